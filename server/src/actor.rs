@@ -1,13 +1,20 @@
 use std::{collections::HashMap, time::Duration};
 
-use actix::{Actor, AsyncContext, Context, Handler, SpawnHandle};
+use actix::{Actor, AsyncContext, Context, Handler, MessageResult, SpawnHandle};
 use log::info;
-use serde_json::{json, Value};
+use serde_json::Value;
 
 use crate::{
     message::*,
-    room::{IfAllReadyResult, RoomData, RoomMeta, GameStage, CHOOSE_TIME, RESULT_TIME, DRAW_TIME},
-    wrap::{PollingProvider, WrapToValue}, app::GRAPHQL_CLIENT, graph::send_graphql,
+    room::{IfAllReadyResult, RoomData, RoomMeta, GameMode, GameStage, RoomCreated, SettingsPatch, random_invite_code, is_valid_tag, DEFAULT_LANGUAGE, DEFAULT_WORD_PACK, MAX_PEOPLE, CHOOSE_TIME, RESULT_TIME, DRAW_TIME},
+    matchmaker::{self, MatchPreference, MatchResult},
+    lobby::{self, RoomPage},
+    player::Player,
+    seat_queue::SEAT_CLAIM_WINDOW,
+    queue::{self, QueueEntry, QueueStatus, QUEUE_WINDOW},
+    vote::{VoteKind, VoteOutcome, VOTE_DURATION},
+    word::{self, WORD_OFFER_COUNT},
+    snapshot::{self, InitSnapshot, PollSnapshot, RoomView, Viewer}, render::CanvasSource, replay::Replay, error::{ApiError, ApiResult}, app::GRAPHQL_CLIENT, graph::send_graphql,
 };
 
 pub struct GameActor {
@@ -16,6 +23,14 @@ pub struct GameActor {
     /// 玩家映射: key: 玩家id， value：房间id
     pub player_room: HashMap<String, String>,
     pub room_spawn_handle: HashMap<String, SpawnHandle>,
+    /// 邀请码映射: key: 邀请码， value：房间id
+    pub invite_codes: HashMap<String, String>,
+    /// 快速匹配队列，按排队先后排列
+    pub queue: Vec<QueueEntry>,
+    /// 快速匹配分配结果: key: 玩家id， value：房间id
+    pub queue_assignments: HashMap<String, String>,
+    /// 队列的分配定时器
+    pub queue_flush_handle: Option<SpawnHandle>,
 }
 
 impl Actor for GameActor {
@@ -28,188 +43,269 @@ impl Default for GameActor {
             rooms: Default::default(),
             player_room: Default::default(),
             room_spawn_handle: Default::default(),
+            invite_codes: Default::default(),
+            queue: vec![],
+            queue_assignments: Default::default(),
+            queue_flush_handle: None,
         }
     }
 }
 
 impl GameActor {
     /// 根据玩家id获取玩家所在房间
-    fn get_player_room(&mut self, user_id: &String) -> Option<&mut RoomData> {
+    fn get_player_room(&mut self, user_id: &String) -> ApiResult<&mut RoomData> {
         if let Some(room_id) = self.player_room.get(user_id) {
-            return self.rooms.get_mut(room_id);
+            return self.rooms.get_mut(room_id).ok_or(ApiError::RoomNotFound);
         }
-        None
+        Err(ApiError::NotInRoom)
+    }
+    /// 玩家加入房间并记录映射
+    fn join_room(&mut self, room_id: &String, player: &Player) -> ApiResult<()> {
+        let room = self.rooms.get_mut(room_id).ok_or(ApiError::RoomNotFound)?;
+        room.check_capacity(&player.user_id)?;
+        room.join_player(player);
+        self.player_room.insert(player.user_id.clone(), room_id.clone());
+        Ok(())
+    }
+    /// 玩家在房间中的身份，没有映射到该房间的 id 按房间外的访客处理
+    fn viewer(&self, room: &RoomData, user_id: Option<&str>) -> Viewer {
+        let user_id = user_id.filter(|id| self.player_room.get(*id) == Some(&room.id));
+        room.viewer(user_id)
+    }
+    /// 根据房间id获取房间
+    fn get_room(&self, room_id: &String) -> ApiResult<&RoomData> {
+        self.rooms.get(room_id).ok_or(ApiError::RoomNotFound)
     }
     fn set_choose_timeout(&mut self, room_id: &String, ctx: &mut <GameActor as Actor>::Context) {
+        let sec = self.rooms.get(room_id).map_or(CHOOSE_TIME, |room| room.settings.choose_time);
         let handle = ctx.notify_later(ChooseTimeoutMsg {
             room_id: room_id.clone()
-        }, Duration::from_secs(CHOOSE_TIME as u64));
+        }, Duration::from_secs(sec as u64));
         self.room_spawn_handle.insert(room_id.clone(), handle);
     }
+    /// 传话模式本步到时进入下一步
+    fn set_telephone_timeout(&mut self, room_id: &String, seq: u32, sec: u8, ctx: &mut <GameActor as Actor>::Context) {
+        let handle = ctx.notify_later(TelephoneStepMsg {
+            room_id: room_id.clone(),
+            seq,
+        }, Duration::from_secs(sec as u64));
+        self.room_spawn_handle.insert(room_id.clone(), handle);
+    }
+    /// 有空座位时留给排队的玩家，并设置留座到期定时器
+    fn offer_seat(&mut self, room_id: &String, ctx: &mut <GameActor as Actor>::Context) {
+        let now = chrono::Local::now().timestamp_millis();
+        if let Some(seq) = self.rooms.get_mut(room_id).and_then(|room| room.offer_seat(now)) {
+            ctx.notify_later(SeatOfferExpireMsg {
+                room_id: room_id.clone(),
+                seq,
+            }, Duration::from_secs(SEAT_CLAIM_WINDOW as u64));
+        }
+    }
     fn cancel_room_spawn(&mut self, room_id: &String, ctx: &mut <GameActor as Actor>::Context) {
         if let Some(handle) = self.room_spawn_handle.remove(room_id) {
             ctx.cancel_future(handle);
         }
     }
+    /// 结算展示结束后轮到下一位玩家
+    fn set_result_timeout(&mut self, room_id: &String, ctx: &mut <GameActor as Actor>::Context) {
+        let handle = ctx.notify_later(NextPlayerDrawMsg {
+            room_id: room_id.clone()
+        }, Duration::from_secs(RESULT_TIME as u64));
+        self.room_spawn_handle.insert(room_id.clone(), handle);
+    }
+    /// 回合被提前结束后，取消原有的定时器，按新的阶段重新设置
+    fn turn_ended(&mut self, room_id: &String, ended: Option<GameStage>, ctx: &mut <GameActor as Actor>::Context) {
+        if ended.is_none() {
+            return
+        }
+        self.cancel_room_spawn(room_id, ctx);
+        match self.rooms.get(room_id).map(|room| room.stage) {
+            Some(GameStage::Choose) => self.set_choose_timeout(room_id, ctx),
+            Some(GameStage::Result) => self.set_result_timeout(room_id, ctx),
+            _ => (),
+        }
+    }
 }
 
 /// 处理创建房间消息
 impl Handler<CreateRoomMsg> for GameActor {
-    type Result = String;
+    type Result = MessageResult<CreateRoomMsg>;
 
-    fn handle(&mut self, _: CreateRoomMsg, _: &mut Self::Context) -> Self::Result {
-        let room_data = RoomData::new();
+    fn handle(&mut self, msg: CreateRoomMsg, _: &mut Self::Context) -> Self::Result {
+        let password = msg.password.filter(|password| !password.is_empty());
+        let room_data = if msg.private || password.is_some() {
+            let mut code = random_invite_code();
+            while self.invite_codes.contains_key(&code) {
+                code = random_invite_code();
+            }
+            RoomData::new_private(password, code)
+        } else {
+            RoomData::new()
+        };
         let id = room_data.id.clone();
+        let invite_code = room_data.invite_code.clone();
+        if let Some(code) = &invite_code {
+            self.invite_codes.insert(code.clone(), id.clone());
+        }
         self.rooms.insert(id.clone(), room_data);
-        id
+        MessageResult(RoomCreated { id, invite_code })
     }
 }
 
 /// 处理玩家加入房间
 impl Handler<JoinRoomMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: JoinRoomMsg, _: &mut Self::Context) -> Self::Result {
-        let JoinRoomMsg { player, room_id, token } = msg;
+        let JoinRoomMsg { player, room_id, token: _, password, invite_code } = msg;
         send_graphql();
-        if let Some(room) = self.rooms.get_mut(&room_id) {
-            room.join_player(&player);
-            self.player_room
-                .insert(player.user_id.clone(), room.id.clone());
-            true
-        } else {
-            false
-        }
+        // 只有邀请码时，通过邀请码找到房间
+        let room_id = match invite_code.as_deref() {
+            Some(code) if room_id.is_empty() => self.invite_codes.get(&code.trim().to_uppercase())
+                .cloned()
+                .ok_or(ApiError::RoomNotFound)?,
+            _ => room_id,
+        };
+        let room = self.rooms.get_mut(&room_id).ok_or(ApiError::RoomNotFound)?;
+        room.check_banned(&player.user_id)?;
+        room.check_access(password.as_deref(), invite_code.as_deref())?;
+        self.join_room(&room_id, &player)
     }
 }
 
 /// 初始化
 impl Handler<InitMsg> for GameActor {
-    type Result = Option<Value>;
+    type Result = ApiResult<InitSnapshot>;
 
     fn handle(&mut self, msg: InitMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            let player = room.players.get(&msg.user_id);
-            if let Some(player) = player {
-                let player = player.to_value(); // 玩家信息
-                let room_meta = RoomMeta::from_room_data(room);
-                let room = room.to_value();
-                return Some(json!({
-                    "room": room,
-                    "user": player,
-                    "meta": room_meta,
-                }));
-            }
-        }
-        Default::default()
+        let room = self.get_player_room(&msg.user_id)?;
+        let player = room.players.get(&msg.user_id).ok_or(ApiError::NotInRoom)?;
+        Ok(InitSnapshot {
+            room: room.view(room.viewer(Some(&msg.user_id))),
+            user: player.clone(), // 玩家信息
+            meta: RoomMeta::from_room_data(room),
+        })
     }
 }
 
 /// 获取房间信息
 impl Handler<GetRoomInfoMsg> for GameActor {
-    type Result = Option<Value>;
+    type Result = ApiResult<RoomView>;
 
     fn handle(&mut self, msg: GetRoomInfoMsg, _: &mut Self::Context) -> Self::Result {
-        let room_id = msg.id;
-        Some(self.rooms.get(&room_id).to_value())
+        let room = self.get_room(&msg.id)?;
+        let viewer = if snapshot::is_admin(msg.admin_token.as_deref()) {
+            Viewer::Admin
+        } else {
+            self.viewer(room, msg.user_id.as_deref())
+        };
+        Ok(room.view(viewer))
     }
 }
 
 /// 玩家准备
 impl Handler<RoomReadyMsg> for GameActor {
-    type Result = ();
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: RoomReadyMsg, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            match room.stage {
-                GameStage::Ready => {
-                    if msg.ready {
-                        room.ready(&msg.user_id);
-                        match room.if_all_ready() {
-                            IfAllReadyResult::Start => {
-                                ctx.notify(GameStartMsg {
-                                    room_id: room.id.clone()
-                                })
-                            },
-                            IfAllReadyResult::AutoStart(sec) => {
-                                ctx.notify(GameStartLaterMsg {
-                                    room_id: room.id.clone(),
-                                    duration: Duration::from_secs(sec as u64),
-                                })
-                            },
-                            _ => (),
-                        }
-                    } else {
-                        room.cacnel_ready(&msg.user_id);
-                        // 有人取消准备则直接取消倒计时
-                        ctx.notify(GameStartCancelMsg {
-                            room_id: room.id.clone()
-                        })
-                    }
+        // 该用户并不在房间内 无法准备
+        let room = self.get_player_room(&msg.user_id)?;
+        if room.stage != GameStage::Ready {
+            return Err(ApiError::WrongStage)
+        }
+        if msg.ready {
+            room.ready(&msg.user_id);
+            match room.if_all_ready() {
+                IfAllReadyResult::Start => {
+                    ctx.notify(GameStartMsg {
+                        room_id: room.id.clone()
+                    })
+                },
+                IfAllReadyResult::AutoStart(sec) => {
+                    ctx.notify(GameStartLaterMsg {
+                        room_id: room.id.clone(),
+                        duration: Duration::from_secs(sec as u64),
+                    })
                 },
                 _ => (),
             }
         } else {
-            // 该用户并不在房间内 无法准备
+            room.cacnel_ready(&msg.user_id);
+            // 有人取消准备则直接取消倒计时
+            ctx.notify(GameStartCancelMsg {
+                room_id: room.id.clone()
+            })
         }
+        Ok(())
     }
 }
 
 /// 站起围观
 impl Handler<RoomGetUpMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
-    fn handle(&mut self, msg: RoomGetUpMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            room.move_to_observer(&msg.user_id);
-            return true;
-        }
-        false
+    fn handle(&mut self, msg: RoomGetUpMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.move_to_observer(&msg.user_id)?;
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
     }
 }
 
 /// 坐下
 impl Handler<RoomSitOnMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
-    fn handle(&mut self, msg: RoomSitOnMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            return room.sit_on(&msg.user_id, msg.pos as usize);
-        }
-        false
+    fn handle(&mut self, msg: RoomSitOnMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.sit_on(&msg.user_id, msg.pos as usize)?;
+        // 排队的玩家坐了别的座位，留给他的座位让给下一位
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
     }
 }
 
 impl Handler<PollingMsg> for GameActor {
-    type Result = Option<Value>;
+    type Result = ApiResult<PollSnapshot>;
 
     fn handle(&mut self, msg: PollingMsg, _: &mut Self::Context) -> Self::Result {
-        Some(
-            self.rooms.get(&msg.room_id).
-                snapshot(&msg.user_id, msg.timestamp ,msg.draw_index)
-        )
+        let room = self.get_room(&msg.room_id)?;
+        let viewer = self.viewer(room, Some(&msg.user_id));
+        let mut snapshot = room.snapshot(viewer, msg.timestamp, msg.draw_index);
+        room.attach_telephone_task(&mut snapshot, &msg.user_id);
+        Ok(snapshot)
     }
 }
 
 impl Handler<ChatMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: ChatMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            room.add_chat(msg.user_id, msg.content);
-            return true;
+        if msg.content.trim().is_empty() {
+            return Err(ApiError::InvalidPayload)
         }
-        false
+        let room = self.get_player_room(&msg.user_id)?;
+        if room.observer.contains(&msg.user_id) {
+            return room.add_spectator_chat(msg.user_id, msg.content)
+        }
+        room.add_chat(msg.user_id, msg.content);
+        Ok(())
     }
 }
 
 impl Handler<LeaveRoomMsg> for GameActor {
-    type Result = ();
+    type Result = ApiResult<()>;
 
-    fn handle(&mut self, msg: LeaveRoomMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            room.player_leave(&msg.user_id)
-        }
+    fn handle(&mut self, msg: LeaveRoomMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        let ended = room.player_leave(&msg.user_id);
+        let room_id = room.id.clone();
+        self.player_room.remove(&msg.user_id);
+        self.turn_ended(&room_id, ended, ctx);
+        self.offer_seat(&room_id, ctx);
+        Ok(())
     }
 }
 
@@ -220,18 +316,43 @@ impl Handler<GetAllRoomMsg> for GameActor {
         let vec = self
             .rooms
             .iter()
+            .filter(|(_, room)| !room.private)
             .map(|(_, room)| RoomMeta::from_room_data(room))
             .collect::<Vec<RoomMeta>>();
         Some(serde_json::to_value(vec).unwrap_or(Default::default()))
     }
 }
 
+/// 分页查询房间列表
+impl Handler<ListRoomsMsg> for GameActor {
+    type Result = ApiResult<RoomPage>;
+
+    fn handle(&mut self, msg: ListRoomsMsg, _: &mut Self::Context) -> Self::Result {
+        lobby::list_rooms(self.rooms.values(), &msg)
+    }
+}
+
 impl Handler<GameStartMsg> for GameActor {
     type Result = ();
 
     fn handle(&mut self, msg: GameStartMsg, ctx: &mut Self::Context) -> Self::Result {
         self.cancel_room_spawn(&msg.room_id, ctx);
         if let Some(room) = self.rooms.get_mut(&msg.room_id) {
+            match room.settings.mode {
+                GameMode::Telephone => {
+                    let now = chrono::Local::now().timestamp_millis();
+                    if let Ok((seq, sec)) = room.start_telephone(now) {
+                        self.set_telephone_timeout(&msg.room_id, seq, sec, ctx);
+                    }
+                    return
+                },
+                // 协作画板没有定时器
+                GameMode::FreeDraw => {
+                    room.start_free_draw();
+                    return
+                },
+                GameMode::Classic => (),
+            }
             room.start();
             self.set_choose_timeout(&msg.room_id, ctx);
         }
@@ -275,17 +396,13 @@ impl Handler<GameStartCancelMsg> for GameActor {
 }
 
 impl Handler<ChooseWordMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: ChooseWordMsg, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            let result = room.pick_a_word(&msg.user_id, msg.word);
-            if result {
-                ctx.notify(SetDrawTimeoutMsg { room_id: room.id.clone() })
-            }
-            return result
-        }
-        false
+        let room = self.get_player_room(&msg.user_id)?;
+        room.pick_a_word(&msg.user_id, msg.word)?;
+        ctx.notify(SetDrawTimeoutMsg { room_id: room.id.clone() });
+        Ok(())
     }
 }
 
@@ -294,21 +411,27 @@ impl Handler<SetDrawTimeoutMsg> for GameActor {
 
     fn handle(&mut self, msg: SetDrawTimeoutMsg, ctx: &mut Self::Context) -> Self::Result {
         self.cancel_room_spawn(&msg.room_id, ctx);
+        let sec = self.rooms.get(&msg.room_id).map_or(DRAW_TIME, |room| room.settings.draw_time);
         let handle = ctx.notify_later(DrawEndMsg{ 
             room_id: msg.room_id.clone() 
-        }, Duration::from_secs(DRAW_TIME as u64));
+        }, Duration::from_secs(sec as u64));
         self.room_spawn_handle.insert(msg.room_id, handle);
     }
 }
 
 impl Handler<DrawMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: DrawMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            return room.draw(&msg.user_id, msg.raw_data, msg.timestamp);
-        }
-        false
+        self.get_player_room(&msg.user_id)?.draw(&msg.user_id, msg.raw_data, msg.timestamp)
+    }
+}
+
+impl Handler<DrawOpMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: DrawOpMsg, _: &mut Self::Context) -> Self::Result {
+        self.get_player_room(&msg.user_id)?.draw_op(&msg.user_id, msg.op, msg.timestamp)
     }
 }
 
@@ -318,10 +441,7 @@ impl Handler<DrawEndMsg> for GameActor {
     fn handle(&mut self, msg: DrawEndMsg, ctx: &mut Self::Context) -> Self::Result {
         if let Some(room) = self.rooms.get_mut(&msg.room_id) {
             room.draw_end();
-            let handle = ctx.notify_later(NextPlayerDrawMsg {
-                room_id: msg.room_id.clone()
-            }, Duration::from_secs(RESULT_TIME as u64));
-            self.room_spawn_handle.insert(msg.room_id, handle);
+            self.set_result_timeout(&msg.room_id, ctx);
         }
     }
 }
@@ -333,55 +453,592 @@ impl Handler<NextPlayerDrawMsg> for GameActor {
         if let Some(room) = self.rooms.get_mut(&msg.room_id) {
             if room.next_player_draw() {
                 self.set_choose_timeout(&msg.room_id, ctx);
+            } else {
+                // 一局结束，游戏中空出的座位留给排队的玩家
+                self.offer_seat(&msg.room_id, ctx);
             }
         }
     }
 }
 
 impl Handler<DrawClearMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: DrawClearMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            return room.clear_draw(&msg.user_id);
-        } else {
-            false
-        }
+        self.get_player_room(&msg.user_id)?.clear_draw(&msg.user_id)
     }
 }
 
 impl Handler<DrawUndoMsg> for GameActor {
-    type Result = bool;
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: DrawUndoMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            return room.undo_draw(&msg.user_id)
-        }
-        false
+        self.get_player_room(&msg.user_id)?.undo_draw(&msg.user_id)
     }
 }
 
 impl Handler<DrawChangeBackgoundMsg> for GameActor {
-    type Result = ();
+    type Result = ApiResult<()>;
 
     fn handle(&mut self, msg: DrawChangeBackgoundMsg, _: &mut Self::Context) -> Self::Result {
-        if let Some(room) = self.get_player_room(&msg.user_id) {
-            room.set_background(&msg.user_id, &msg.color);
-        }
+        self.get_player_room(&msg.user_id)?.set_background(&msg.user_id, &msg.color)
     }
 }
 
 impl Handler<FindJoinableRoomMsg> for GameActor {
-    type Result = Option<Value>;
+    type Result = ApiResult<MatchResult>;
+
+    fn handle(&mut self, msg: FindJoinableRoomMsg, _: &mut Self::Context) -> Self::Result {
+        let preference = MatchPreference {
+            language: msg.language,
+            word_pack: msg.word_pack,
+        };
+        if !preference.is_valid() {
+            return Err(ApiError::InvalidPayload)
+        }
+        let player = match (msg.user_id, msg.nick_name) {
+            (Some(user_id), Some(nick_name)) => Some(Player {
+                user_id,
+                nick_name,
+                avatar_url: msg.avatar_url.unwrap_or_default(),
+            }),
+            _ => None,
+        };
+        let user_id = player.as_ref().map(|player| &player.user_id);
+        let found = matchmaker::pick_room(self.rooms.values(), &preference, user_id)
+            .map(|room| room.id.clone());
+        let created = found.is_none();
+        let room_id = match found {
+            Some(id) => id,
+            // 只有玩家随即加入时才新建，匿名查询不会留下空房间
+            None if player.is_some() => {
+                let room = preference.new_room();
+                let id = room.id.clone();
+                self.rooms.insert(id.clone(), room);
+                id
+            },
+            None => return Err(ApiError::NoJoinableRoom),
+        };
+        if let Some(player) = &player {
+            if let Err(e) = self.join_room(&room_id, player) {
+                if created {
+                    self.rooms.remove(&room_id);
+                }
+                return Err(e)
+            }
+        }
+        Ok(MatchResult {
+            room_id,
+            created,
+            joined: player.is_some(),
+        })
+    }
+}
+
+impl Handler<RenderCanvasMsg> for GameActor {
+    type Result = ApiResult<CanvasSource>;
+
+    fn handle(&mut self, msg: RenderCanvasMsg, _: &mut Self::Context) -> Self::Result {
+        let room = self.get_room(&msg.room_id)?;
+        Ok(CanvasSource {
+            draw_data: room.draw_data.clone(),
+            background: room.background.clone(),
+        })
+    }
+}
+
+impl Handler<RoundThumbnailMsg> for GameActor {
+    type Result = ApiResult<CanvasSource>;
+
+    fn handle(&mut self, msg: RoundThumbnailMsg, _: &mut Self::Context) -> Self::Result {
+        let room = self.get_room(&msg.room_id)?;
+        let record = room.gallery.get(msg.round).ok_or(ApiError::RoundNotFound)?;
+        Ok(record.canvas())
+    }
+}
+
+impl Handler<GetGalleryMsg> for GameActor {
+    type Result = ApiResult<Value>;
+
+    fn handle(&mut self, msg: GetGalleryMsg, _: &mut Self::Context) -> Self::Result {
+        let room = self.get_room(&msg.room_id)?;
+        let list = room.gallery.iter()
+            .map(|record| record.summary())
+            .collect::<Vec<_>>();
+        serde_json::to_value(list).map_err(|_| ApiError::Internal)
+    }
+}
+
+impl Handler<GetGalleryRoundMsg> for GameActor {
+    type Result = ApiResult<Value>;
+
+    fn handle(&mut self, msg: GetGalleryRoundMsg, _: &mut Self::Context) -> Self::Result {
+        let room = self.get_room(&msg.room_id)?;
+        let record = room.gallery.get(msg.round).ok_or(ApiError::RoundNotFound)?;
+        serde_json::to_value(record).map_err(|_| ApiError::Internal)
+    }
+}
+
+impl Handler<GetReplayMsg> for GameActor {
+    type Result = ApiResult<Replay>;
+
+    fn handle(&mut self, msg: GetReplayMsg, _: &mut Self::Context) -> Self::Result {
+        let room = self.get_room(&msg.room_id)?;
+        room.gallery.get(msg.round).map(Replay::from_record).ok_or(ApiError::RoundNotFound)
+    }
+}
+
+/// 踢人
+impl Handler<KickPlayerMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: KickPlayerMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        let ended = room.kick(&msg.user_id, &msg.target_id)?;
+        let room_id = room.id.clone();
+        self.player_room.remove(&msg.target_id);
+        self.turn_ended(&room_id, ended, ctx);
+        self.offer_seat(&room_id, ctx);
+        Ok(())
+    }
+}
+
+/// 封禁
+impl Handler<BanPlayerMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: BanPlayerMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        let in_room = room.players.contains_key(&msg.target_id);
+        let ended = room.ban(&msg.user_id, &msg.target_id)?;
+        let room_id = room.id.clone();
+        if in_room {
+            self.player_room.remove(&msg.target_id);
+            self.turn_ended(&room_id, ended, ctx);
+            self.offer_seat(&room_id, ctx);
+        }
+        Ok(())
+    }
+}
+
+/// 转让房主
+impl Handler<TransferOwnerMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: TransferOwnerMsg, _: &mut Self::Context) -> Self::Result {
+        self.get_player_room(&msg.user_id)?.transfer_owner(&msg.user_id, &msg.target_id)
+    }
+}
+
+/// 锁定座位
+impl Handler<LockSeatMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: LockSeatMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.lock_seat(&msg.user_id, msg.pos as usize, msg.locked)?;
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
+    }
+}
+
+/// 修改房间设置
+impl Handler<UpdateSettingsMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: UpdateSettingsMsg, _: &mut Self::Context) -> Self::Result {
+        let UpdateSettingsMsg {
+            user_id, name, choose_time, draw_time, language, word_pack,
+            max_observers, observer_chat, guess_along, mid_game_join, late_join_score,
+            mode, team_mode, steal_delay, custom_word_ratio,
+        } = msg;
+        let patch = SettingsPatch {
+            name, choose_time, draw_time, language, word_pack,
+            max_observers, observer_chat, guess_along, mid_game_join, late_join_score,
+            mode, team_mode, steal_delay, custom_word_ratio,
+        };
+        self.get_player_room(&user_id)?.update_settings(&user_id, patch)
+    }
+}
+
+/// 房主强制开始
+impl Handler<ForceStartMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: ForceStartMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.check_force_start(&msg.user_id)?;
+        ctx.notify(GameStartMsg { room_id: room.id.clone() });
+        Ok(())
+    }
+}
+
+impl GameActor {
+    /// 处理投票结果：进行中则设置到期定时器，通过则执行
+    fn apply_vote(&mut self, room_id: &String, outcome: VoteOutcome, ctx: &mut <GameActor as Actor>::Context) {
+        match outcome {
+            VoteOutcome::Pending(seq) => {
+                ctx.notify_later(VoteExpireMsg {
+                    room_id: room_id.clone(),
+                    seq,
+                }, Duration::from_secs(VOTE_DURATION as u64));
+            },
+            VoteOutcome::Passed(VoteKind::Kick { target_id }) => {
+                if let Some(room) = self.rooms.get_mut(room_id) {
+                    let ended = room.player_leave(&target_id);
+                    self.player_room.remove(&target_id);
+                    self.turn_ended(room_id, ended, ctx);
+                    self.offer_seat(room_id, ctx);
+                }
+            },
+            VoteOutcome::Passed(VoteKind::Skip { drawer_id }) => {
+                // 投票期间已经换人时不再跳过
+                let ended = self.rooms.get_mut(room_id)
+                    .filter(|room| room.cur_id.as_ref() == Some(&drawer_id))
+                    .and_then(|room| room.end_turn());
+                self.turn_ended(room_id, ended, ctx);
+            },
+            VoteOutcome::Failed => (),
+        }
+    }
+}
+
+/// 发起投票
+impl Handler<StartVoteMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: StartVoteMsg, ctx: &mut Self::Context) -> Self::Result {
+        let now = chrono::Local::now().timestamp_millis();
+        let room = self.get_player_room(&msg.user_id)?;
+        let outcome = room.start_vote(&msg.user_id, msg.kind, msg.target_id, now)?;
+        let room_id = room.id.clone();
+        self.apply_vote(&room_id, outcome, ctx);
+        Ok(())
+    }
+}
+
+/// 投票
+impl Handler<CastVoteMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: CastVoteMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        let outcome = room.cast_vote(&msg.user_id, msg.agree)?;
+        let room_id = room.id.clone();
+        // 进行中的投票已经有定时器了
+        if !matches!(outcome, VoteOutcome::Pending(_)) {
+            self.apply_vote(&room_id, outcome, ctx);
+        }
+        Ok(())
+    }
+}
+
+/// 投票到期
+impl Handler<VoteExpireMsg> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: VoteExpireMsg, _: &mut Self::Context) -> Self::Result {
+        if let Some(room) = self.rooms.get_mut(&msg.room_id) {
+            room.expire_vote(msg.seq);
+        }
+    }
+}
+
+impl GameActor {
+    fn queue_status(&self, user_id: &String) -> QueueStatus {
+        if let Some(position) = self.queue.iter().position(|entry| &entry.player.user_id == user_id) {
+            QueueStatus::Waiting { position: position + 1, size: self.queue.len() }
+        } else if let Some(room_id) = self.queue_assignments.get(user_id) {
+            QueueStatus::Matched { room_id: room_id.clone() }
+        } else {
+            QueueStatus::Idle
+        }
+    }
+}
+
+/// 加入快速匹配队列
+impl Handler<JoinQueueMsg> for GameActor {
+    type Result = ApiResult<QueueStatus>;
+
+    fn handle(&mut self, msg: JoinQueueMsg, ctx: &mut Self::Context) -> Self::Result {
+        let JoinQueueMsg { user_id, nick_name, avatar_url, language } = msg;
+        let language = language.unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+        if !is_valid_tag(&language) || nick_name.trim().is_empty() {
+            return Err(ApiError::InvalidPayload)
+        }
+        if self.player_room.contains_key(&user_id) {
+            return Err(ApiError::AlreadyInRoom)
+        }
+        let now = chrono::Local::now().timestamp_millis();
+        match self.queue.iter_mut().find(|entry| entry.player.user_id == user_id) {
+            Some(entry) => entry.last_seen = now,
+            None => {
+                self.queue_assignments.remove(&user_id);
+                self.queue.push(QueueEntry {
+                    player: Player {
+                        user_id: user_id.clone(),
+                        nick_name,
+                        avatar_url,
+                    },
+                    language: language.clone(),
+                    last_seen: now,
+                });
+            },
+        }
+        let same_language = self.queue.iter().filter(|entry| entry.language == language).count();
+        if same_language >= MAX_PEOPLE {
+            // 人满了不用等时间窗口
+            ctx.notify(FlushQueueMsg {});
+        } else if self.queue_flush_handle.is_none() {
+            let handle = ctx.notify_later(FlushQueueMsg {}, Duration::from_secs(QUEUE_WINDOW as u64));
+            self.queue_flush_handle = Some(handle);
+        }
+        Ok(self.queue_status(&user_id))
+    }
+}
+
+/// 离开快速匹配队列
+impl Handler<LeaveQueueMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: LeaveQueueMsg, _: &mut Self::Context) -> Self::Result {
+        self.queue.retain(|entry| entry.player.user_id != msg.user_id);
+        self.queue_assignments.remove(&msg.user_id);
+        Ok(())
+    }
+}
+
+/// 查询排队状态
+impl Handler<QueueStatusMsg> for GameActor {
+    type Result = ApiResult<QueueStatus>;
+
+    fn handle(&mut self, msg: QueueStatusMsg, _: &mut Self::Context) -> Self::Result {
+        let now = chrono::Local::now().timestamp_millis();
+        if let Some(entry) = self.queue.iter_mut().find(|entry| entry.player.user_id == msg.user_id) {
+            entry.last_seen = now;
+        }
+        Ok(self.queue_status(&msg.user_id))
+    }
+}
 
-    fn handle(&mut self, _: FindJoinableRoomMsg, _: &mut Self::Context) -> Self::Result {
-        for (room_id, room_data) in self.rooms.iter() {
-            if room_data.can_join() {
-                return Some(json!({
-                    "roomId": room_id
-                }));
+/// 把排队的玩家分配到新房间，坐下并自动准备
+impl Handler<FlushQueueMsg> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, _: FlushQueueMsg, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(handle) = self.queue_flush_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        // 不再轮询的玩家，以及排队期间自己进了房间的玩家移出队列
+        let now = chrono::Local::now().timestamp_millis();
+        let player_room = &self.player_room;
+        self.queue.retain(|entry| !entry.is_stale(now) && !player_room.contains_key(&entry.player.user_id));
+        let entries = std::mem::take(&mut self.queue);
+        let (rooms, rest) = queue::form_rooms(entries);
+        self.queue = rest;
+        for entries in rooms {
+            let mut room = MatchPreference {
+                language: Some(entries[0].language.clone()),
+                word_pack: None,
+            }.new_room();
+            for entry in entries.iter() {
+                room.join_player(&entry.player);
+                room.ready(&entry.player.user_id);
+                self.player_room.insert(entry.player.user_id.clone(), room.id.clone());
+                self.queue_assignments.insert(entry.player.user_id.clone(), room.id.clone());
+            }
+            let room_id = room.id.clone();
+            match room.if_all_ready() {
+                IfAllReadyResult::Start => ctx.notify(GameStartMsg { room_id: room_id.clone() }),
+                IfAllReadyResult::AutoStart(sec) => ctx.notify(GameStartLaterMsg {
+                    room_id: room_id.clone(),
+                    duration: Duration::from_secs(sec as u64),
+                }),
+                _ => (),
             }
+            info!("quick play room {} with {} players", room_id, entries.len());
+            self.rooms.insert(room_id, room);
+        }
+        // 人数不够的继续等下一个窗口
+        if !self.queue.is_empty() {
+            let handle = ctx.notify_later(FlushQueueMsg {}, Duration::from_secs(QUEUE_WINDOW as u64));
+            self.queue_flush_handle = Some(handle);
+        }
+    }
+}
+
+/// 排队等座位
+impl Handler<JoinSeatQueueMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: JoinSeatQueueMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.enqueue_seat(&msg.user_id)?;
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
+    }
+}
+
+/// 退出座位排队
+impl Handler<LeaveSeatQueueMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: LeaveSeatQueueMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.dequeue_seat(&msg.user_id);
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
+    }
+}
+
+/// 留座到期，让给下一位
+impl Handler<SeatOfferExpireMsg> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SeatOfferExpireMsg, ctx: &mut Self::Context) -> Self::Result {
+        let expired = self.rooms.get_mut(&msg.room_id)
+            .map_or(false, |room| room.expire_seat_offer(msg.seq));
+        if expired {
+            self.offer_seat(&msg.room_id, ctx);
         }
-        None
     }
-}
\ No newline at end of file
+}
+
+/// 选择队伍
+impl Handler<ChooseTeamMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: ChooseTeamMsg, _: &mut Self::Context) -> Self::Result {
+        self.get_player_room(&msg.user_id)?.choose_team(&msg.user_id, msg.team)
+    }
+}
+
+/// 传话模式提交本步，所有人都提交了直接进入下一步
+impl Handler<TelephoneSubmitMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: TelephoneSubmitMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        let all_submitted = room.telephone_submit(&msg.user_id, msg.text)?;
+        if all_submitted {
+            let room_id = room.id.clone();
+            let seq = room.telephone_seq;
+            self.cancel_room_spawn(&room_id, ctx);
+            ctx.notify(TelephoneStepMsg { room_id, seq });
+        }
+        Ok(())
+    }
+}
+
+/// 传话模式进入下一步
+impl Handler<TelephoneStepMsg> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: TelephoneStepMsg, ctx: &mut Self::Context) -> Self::Result {
+        let now = chrono::Local::now().timestamp_millis();
+        let next = self.rooms.get_mut(&msg.room_id)
+            .and_then(|room| room.advance_telephone(msg.seq, now));
+        match next {
+            Some((seq, sec)) => self.set_telephone_timeout(&msg.room_id, seq, sec, ctx),
+            None => self.offer_seat(&msg.room_id, ctx),
+        }
+    }
+}
+
+/// 房主结束协作画板
+impl Handler<EndFreeDrawMsg> for GameActor {
+    type Result = ApiResult<()>;
+
+    fn handle(&mut self, msg: EndFreeDrawMsg, ctx: &mut Self::Context) -> Self::Result {
+        let room = self.get_player_room(&msg.user_id)?;
+        room.end_free_draw(&msg.user_id)?;
+        let room_id = room.id.clone();
+        self.offer_seat(&room_id, ctx);
+        Ok(())
+    }
+}
+
+/// 房主上传自定义词库
+impl Handler<SetCustomWordsMsg> for GameActor {
+    type Result = ApiResult<usize>;
+
+    fn handle(&mut self, msg: SetCustomWordsMsg, _: &mut Self::Context) -> Self::Result {
+        self.get_player_room(&msg.user_id)?.set_custom_words(&msg.user_id, &msg.words)
+    }
+}
+
+/// 按玩家所在房间的词库与自定义词库出词，不在房间内时用默认词库
+impl Handler<RandomWordsMsg> for GameActor {
+    type Result = MessageResult<RandomWordsMsg>;
+
+    fn handle(&mut self, msg: RandomWordsMsg, _: &mut Self::Context) -> Self::Result {
+        let room = msg.user_id.as_ref()
+            .and_then(|user_id| self.player_room.get(user_id))
+            .and_then(|room_id| self.rooms.get(room_id));
+        let packs = word::word_packs();
+        MessageResult(match room {
+            Some(room) => room.offer_words(packs.random(&room.settings.word_pack, WORD_OFFER_COUNT)),
+            None => packs.random(DEFAULT_WORD_PACK, WORD_OFFER_COUNT),
+        })
+    }
+}
+
+#[test]
+fn test_viewer_requires_membership() {
+    use actix::System;
+
+    System::new("test").block_on(async {
+        let mut actor = GameActor::default();
+        let room = RoomData::new();
+        let room_id = room.id.clone();
+        actor.rooms.insert(room_id.clone(), room);
+        actor.join_room(&room_id, &Player::test("1")).unwrap();
+        // 房间数据里有，但没有映射到本房间的 id
+        actor.rooms.get_mut(&room_id).unwrap().join_player(&Player::test("2"));
+        let addr = actor.start();
+        addr.send(ChatMsg { user_id: "1".into(), content: "你好".into() }).await.unwrap().unwrap();
+
+        let info = |user_id: &str| GetRoomInfoMsg {
+            id: room_id.clone(),
+            user_id: Some(user_id.to_string()),
+            admin_token: None,
+        };
+        assert!(addr.send(info("1")).await.unwrap().unwrap().chat_history.is_some());
+        assert!(addr.send(info("2")).await.unwrap().unwrap().chat_history.is_none());
+
+        let poll = |user_id: &str| PollingMsg {
+            user_id: user_id.to_string(),
+            room_id: room_id.clone(),
+            timestamp: 0,
+            draw_index: 0,
+        };
+        assert_eq!(addr.send(poll("1")).await.unwrap().unwrap().chat_history.len(), 1);
+        assert!(addr.send(poll("2")).await.unwrap().unwrap().chat_history.is_empty());
+    });
+}
+
+#[test]
+fn test_find_joinable_room() {
+    use actix::System;
+
+    System::new("test").block_on(async {
+        let addr = GameActor::default().start();
+        let find = |user_id: Option<&str>| FindJoinableRoomMsg {
+            language: None,
+            word_pack: None,
+            user_id: user_id.map(|id| id.to_string()),
+            nick_name: user_id.map(|id| id.to_string()),
+            avatar_url: None,
+        };
+        // 匿名查询不会新建房间
+        assert_eq!(addr.send(find(None)).await.unwrap().unwrap_err(), ApiError::NoJoinableRoom);
+        let created = addr.send(find(Some("1"))).await.unwrap().unwrap();
+        assert!(created.created && created.joined);
+        let found = addr.send(find(None)).await.unwrap().unwrap();
+        assert_eq!(found.room_id, created.room_id);
+        assert!(!found.created && !found.joined);
+    });
+}
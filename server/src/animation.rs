@@ -0,0 +1,284 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::{render::{self, Canvas}, replay::{Replay, RoundAction}};
+
+/// 帧率上限
+pub const MAX_FPS: u16 = 30;
+/// 动画时长上限（秒）
+pub const MAX_ANIMATION_DURATION: u32 = 60;
+/// 动画帧数上限
+pub const MAX_FRAMES: i64 = 300;
+/// 动画全部帧的像素总数上限，即帧数 × 边长²
+pub const MAX_TOTAL_PIXELS: i64 = 40_000_000;
+/// 每画这么多笔保存一次画布，撤销时从最近的存档继续画
+const CHECKPOINT_INTERVAL: usize = 64;
+/// 画布存档占用内存的上限（字节）
+const CHECKPOINT_BUDGET: usize = 64 * 1024 * 1024;
+/// 最后一帧的停留时间（毫秒）
+const LAST_FRAME_DELAY: u16 = 2000;
+
+/// 动画导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl Default for AnimationFormat {
+    fn default() -> Self {
+        AnimationFormat::Gif
+    }
+}
+
+/// 动画导出参数
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AnimationOptions {
+    /// 导出格式，由路由决定
+    #[serde(skip)]
+    pub format: AnimationFormat,
+    /// 帧率
+    #[serde(default = "default_fps")]
+    pub fps: u16,
+    /// 最大时长（秒），回合更长时按比例加速
+    #[serde(default = "default_max_duration")]
+    pub max_duration: u32,
+    /// 图片边长
+    #[serde(default = "default_size")]
+    pub size: u32,
+}
+
+fn default_fps() -> u16 { 10 }
+fn default_max_duration() -> u32 { 10 }
+fn default_size() -> u32 { 250 }
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            format: Default::default(),
+            fps: default_fps(),
+            max_duration: default_max_duration(),
+            size: default_size(),
+        }
+    }
+}
+
+impl AnimationOptions {
+    /// 在帧数上限与像素预算内最多的帧数，至少保留首尾两帧
+    fn max_frames(&self) -> i64 {
+        let size = render::clamp_size(self.size) as i64;
+        (MAX_TOTAL_PIXELS / (size * size)).min(MAX_FRAMES).max(2)
+    }
+
+    /// 每一帧对应的回合时间点（毫秒）
+    fn frame_offsets(&self, replay: &Replay) -> Vec<i64> {
+        let fps = self.fps.max(1).min(MAX_FPS) as i64;
+        let max_duration = self.max_duration.max(1).min(MAX_ANIMATION_DURATION) as i64 * 1000;
+        // 以最后一次改变画布的时间作为结束，避免结尾出现长时间的静止
+        let end = replay.timeline.iter()
+            .filter(|entry| !matches!(entry.action, RoundAction::Chat { .. } | RoundAction::Score { .. }))
+            .map(|entry| entry.offset)
+            .max()
+            .unwrap_or(0);
+        let playback = end.min(max_duration);
+        let count = (playback * fps / 1000).max(1).min(self.max_frames() - 1);
+        (0..=count).map(|i| end * i / count).collect()
+    }
+}
+
+/// 逐帧回放画布，`f` 接收每一帧的画布。
+/// 撤销与清空只会去掉末尾的笔画，此时从之前保存的画布继续画，不必从头回放
+fn for_each_frame<F>(replay: &Replay, offsets: &[i64], size: u32, mut f: F) -> Option<()>
+where
+    F: FnMut(&Canvas) -> Option<()>,
+{
+    let size = render::clamp_size(size);
+    let max_checkpoints = CHECKPOINT_BUDGET / (size as usize * size as usize * 3);
+    // 当前可见的笔画
+    let mut ops = vec![];
+    let mut background = "#ffffff";
+    let mut canvas = Canvas::new(size, background);
+    // 画布上画了 `ops` 的前 `painted` 笔，其中前 `valid` 笔仍然可见
+    let mut painted = 0;
+    let mut valid = 0;
+    let mut repaint = false;
+    // (笔画数, 画布)，按笔画数递增
+    let mut checkpoints: Vec<(usize, Canvas)> = vec![];
+    let mut cursor = 0;
+    for &offset in offsets {
+        while cursor < replay.timeline.len() && replay.timeline[cursor].offset <= offset {
+            match &replay.timeline[cursor].action {
+                RoundAction::Draw { op } if op.validate() => ops.push(op),
+                RoundAction::Undo => {
+                    ops.pop();
+                },
+                RoundAction::Clear => ops.clear(),
+                RoundAction::Background { color } => {
+                    background = color.as_str();
+                    checkpoints.clear();
+                    repaint = true;
+                },
+                _ => (),
+            }
+            valid = valid.min(ops.len());
+            cursor += 1;
+        }
+        if repaint || valid < painted {
+            checkpoints.retain(|(count, _)| *count <= valid);
+            match checkpoints.last() {
+                Some((count, saved)) => {
+                    canvas = saved.clone();
+                    painted = *count;
+                },
+                None => {
+                    canvas = Canvas::new(size, background);
+                    painted = 0;
+                },
+            }
+            repaint = false;
+        }
+        for op in &ops[painted..] {
+            canvas.apply(op);
+            painted += 1;
+            if painted % CHECKPOINT_INTERVAL == 0 && checkpoints.len() < max_checkpoints {
+                checkpoints.push((painted, canvas.clone()));
+            }
+        }
+        valid = painted;
+        f(&canvas)?;
+    }
+    Some(())
+}
+
+/// 把回合的绘制过程编码为动画
+pub fn encode(replay: &Replay, options: &AnimationOptions) -> Option<Vec<u8>> {
+    let offsets = options.frame_offsets(replay);
+    let frame_delay = 1000 / options.fps.max(1).min(MAX_FPS);
+    let last = offsets.len() - 1;
+    let mut buffer = Vec::new();
+    match options.format {
+        AnimationFormat::Gif => {
+            let size = render::clamp_size(options.size) as u16;
+            let mut encoder = gif::Encoder::new(&mut buffer, size, size, &[]).ok()?;
+            encoder.set_repeat(gif::Repeat::Infinite).ok()?;
+            let mut index = 0;
+            for_each_frame(replay, &offsets, options.size, |canvas| {
+                let mut frame = gif::Frame::from_rgb_speed(size, size, &canvas.rgb(), 10);
+                // gif 的延时单位为 10 毫秒
+                frame.delay = (if index == last { LAST_FRAME_DELAY } else { frame_delay }) / 10;
+                index += 1;
+                encoder.write_frame(&frame).ok()
+            })?;
+        },
+        AnimationFormat::Apng => {
+            let size = render::clamp_size(options.size);
+            let mut encoder = png::Encoder::new(&mut buffer, size, size);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(offsets.len() as u32, 0).ok()?;
+            let mut writer = encoder.write_header().ok()?;
+            let mut index = 0;
+            for_each_frame(replay, &offsets, options.size, |canvas| {
+                let delay = if index == last { LAST_FRAME_DELAY } else { frame_delay };
+                index += 1;
+                writer.set_frame_delay(delay, 1000).ok()?;
+                writer.write_image_data(&canvas.rgb()).ok()
+            })?;
+            writer.finish().ok()?;
+        },
+    }
+    Some(buffer)
+}
+
+#[test]
+fn test_encode_animation() {
+    use std::collections::HashMap;
+    use crate::{draw_data::{DrawOp, Point}, replay::TimelineEntry};
+
+    let line = |offset: i64, x: i32| TimelineEntry {
+        offset,
+        action: RoundAction::Draw {
+            op: DrawOp::Line {
+                from: Point { x, y: 0 },
+                to: Point { x, y: 100 },
+                color: "#000000".into(),
+                width: 2,
+            },
+        },
+    };
+    let replay = Replay {
+        version: crate::replay::REPLAY_VERSION,
+        round: 0,
+        word: "苹果".into(),
+        drawer_id: "1".into(),
+        drawer_name: None,
+        duration: 2000,
+        guessers: vec![],
+        scores: HashMap::new(),
+        timeline: vec![line(0, 10), line(500, 20), line(1000, 30)],
+    };
+    let options = AnimationOptions { fps: 4, size: 32, ..Default::default() };
+    assert_eq!(options.frame_offsets(&replay), vec![0, 250, 500, 750, 1000]);
+
+    let gif = encode(&replay, &options).unwrap();
+    assert!(gif.starts_with(b"GIF89a"));
+    let apng = encode(&replay, &AnimationOptions { format: AnimationFormat::Apng, ..options }).unwrap();
+    assert!(apng.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // acTL 块记录帧数
+    let actl = apng.windows(4).position(|w| w == b"acTL").unwrap();
+    assert_eq!(&apng[actl + 4..actl + 8], &5u32.to_be_bytes());
+
+    // 大尺寸、高帧率时按像素预算减少帧数
+    let options = AnimationOptions { fps: MAX_FPS, max_duration: MAX_ANIMATION_DURATION, size: 2048, ..Default::default() };
+    let replay = Replay { timeline: vec![line(0, 10), line(60_000, 20)], ..replay };
+    let offsets = options.frame_offsets(&replay);
+    assert_eq!(offsets.len() as i64, options.max_frames());
+    assert!(offsets.len() as i64 * 2048 * 2048 <= MAX_TOTAL_PIXELS);
+}
+
+#[test]
+fn test_frames_match_replay() {
+    use std::collections::HashMap;
+    use crate::{draw_data::{DrawOp, Point}, replay::TimelineEntry};
+
+    let entry = |offset: i64, action: RoundAction| TimelineEntry { offset, action };
+    let mut timeline = vec![];
+    for i in 0..200 {
+        timeline.push(entry(i * 10, RoundAction::Draw {
+            op: DrawOp::Line {
+                from: Point { x: (i * 7 % 500) as i32, y: 0 },
+                to: Point { x: (i * 13 % 500) as i32, y: 500 },
+                color: if i % 2 == 0 { "#000000".into() } else { "#ff0000".into() },
+                width: 3,
+            },
+        }));
+        if i % 50 == 49 {
+            timeline.push(entry(i * 10, RoundAction::Undo));
+            timeline.push(entry(i * 10, RoundAction::Undo));
+        }
+    }
+    timeline.push(entry(1200, RoundAction::Background { color: "#eeeeee".into() }));
+    timeline.push(entry(1500, RoundAction::Clear));
+    timeline.sort_by_key(|entry| entry.offset);
+    let replay = Replay {
+        version: crate::replay::REPLAY_VERSION,
+        round: 0,
+        word: "苹果".into(),
+        drawer_id: "1".into(),
+        drawer_name: None,
+        duration: 2000,
+        guessers: vec![],
+        scores: HashMap::new(),
+        timeline,
+    };
+    let offsets = (0..=40).map(|i| i * 50).collect::<Vec<_>>();
+    let mut index = 0;
+    for_each_frame(&replay, &offsets, 32, |canvas| {
+        let (draw_data, background) = replay.canvas_at(offsets[index]);
+        assert_eq!(canvas.rgb(), render::render(&draw_data, &background, 32).rgb(), "frame {}", index);
+        index += 1;
+        Some(())
+    }).unwrap();
+    assert_eq!(index, offsets.len());
+}
@@ -1,26 +1,31 @@
 use actix_cors::Cors;
-use actix_web::{App, HttpResponse, HttpServer, Responder, get, web::{self, Data}, post, client::Client, header::CONTENT_TYPE};
-use actix::{Actor, Addr};
+use actix_web::{App, HttpResponse, HttpServer, Responder, ResponseError, get, web::{self, Data}, post, client::Client, header::{AUTHORIZATION, CONTENT_TYPE}, error::{InternalError, JsonPayloadError}, HttpRequest};
+use actix::{Actor, Addr, Handler, Message};
 use log::info;
-use serde_json::json;
-use crate::{actor::GameActor, message::*, wrap::{WrapMailboxErrorResponse, WrapMailboxErrorResult, WarpSuccResponse}, word::WordEngine};
+use crate::{actor::GameActor, message::*, wrap::{WrapMailboxErrorResponse, WrapMailboxErrorResult, WarpSuccResponse, WrapApiResult, WrapApiResponse}, error::{ApiError, ApiResult}, word::{self, ReloadWordsResult}, snapshot, render::{CanvasFormat, CanvasSource, THUMBNAIL_SIZE}, replay::Replay, draw_data::MAX_POINTS, animation::{self, AnimationFormat, AnimationOptions}, openapi};
 
 pub async fn run() -> std::io::Result<()> {
     let (addr, port) = ("0.0.0.0", "8000");
 
     let client: Client = Client::builder().header(CONTENT_TYPE, "application/json").finish();
+
+    // 词库只在启动时加载一次，之后通过管理接口重新加载
+    let packs = word::reload()?;
+    info!("word packs loaded: {:?}", packs.summary());
     
     HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin();
 
-        let word_engine = WordEngine::configure("words.txt");
-
         App::new()
             .wrap(cors)
             .app_data(Data::new(ADDR.clone()))
-            .app_data(Data::new(word_engine))
             .app_data(Data::new(client))
+            // 参数解析失败时返回统一的错误结构
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                InternalError::from_response(err, ApiError::InvalidPayload.error_response()).into()
+            }))
+            .app_data(web::JsonConfig::default().error_handler(json_error_handler))
             .default_service(
                 web::route().to(|| HttpResponse::NotFound().finish())
             )
@@ -29,6 +34,7 @@ pub async fn run() -> std::io::Result<()> {
                 .service(create_room) // 创建房间
                 .service(join_room) // 加入房间
                 .service(get_all_room) // 获取所有房间
+                .service(list_rooms) // 房间列表
                 .service(init) // 初始化
                 .service(leave_room) // 离开房间
                 .service(client_poll) // 客户端轮训
@@ -40,11 +46,92 @@ pub async fn run() -> std::io::Result<()> {
                 .service(get_random_words) // 获取随机词库
                 .service(choose_a_word) // 选词
                 .service(draw) // 画图
+                .service(draw_op) // 图形绘制
                 .service(undo) // 撤销
                 .service(clear) // 清空
                 .service(set_color) // 设置颜色
+                .service(kick_player) // 踢人
+                .service(ban_player) // 封禁玩家
+                .service(transfer_owner) // 转让房主
+                .service(lock_seat) // 锁定座位
+                .service(update_settings) // 修改房间设置
+                .service(force_start) // 强制开始
+                .service(start_vote) // 发起投票
+                .service(cast_vote) // 投票
+                .service(join_queue) // 快速匹配排队
+                .service(queue_status) // 排队状态
+                .service(leave_queue) // 取消排队
+                .service(join_seat_queue) // 排队等座位
+                .service(leave_seat_queue) // 退出座位排队
+                .service(choose_team) // 选择队伍
+                .service(telephone_submit) // 传话模式提交
+                .service(end_free_draw) // 结束协作画板
+                .service(random_api) // 随机进房
+                .service(canvas_png) // 画布导出 png
+                .service(canvas_svg) // 画布导出 svg
+                .service(round_thumbnail) // 回合缩略图
+                .service(get_gallery) // 本局画廊
+                .service(get_gallery_round) // 画廊回合详情
+                .service(get_replay) // 回合回放
+                .service(export_replay) // 导出回放文件
+                .service(import_replay) // 导入回放文件
+                .service(replay_gif) // 绘制过程动画 gif
+                .service(replay_apng) // 绘制过程动画 apng
+                .service(openapi_spec) // 接口文档
+            )
+            .service(
+                // 所有修改类接口使用 POST + json，查询类接口与 v1 一致
+                web::scope("/v2")
+                .app_data(v2_json_config())
+                .service(v2::create_room) // 创建房间
+                .service(join_room) // 加入房间
+                .service(v2::leave_room) // 离开房间
+                .service(v2::set_ready) // 房间玩家准备
+                .service(v2::get_up) // 站起围观
+                .service(v2::sit_on) // 坐下
+                .service(v2::chat) // 聊天
+                .service(v2::choose_a_word) // 选词
+                .service(v2::draw) // 画图
+                .service(draw_op) // 图形绘制
+                .service(v2::undo) // 撤销
+                .service(v2::clear) // 清空
+                .service(v2::set_color) // 设置颜色
+                .service(v2::kick_player) // 踢人
+                .service(v2::ban_player) // 封禁玩家
+                .service(v2::transfer_owner) // 转让房主
+                .service(v2::lock_seat) // 锁定座位
+                .service(v2::update_settings) // 修改房间设置
+                .service(v2::force_start) // 强制开始
+                .service(v2::start_vote) // 发起投票
+                .service(v2::cast_vote) // 投票
+                .service(v2::join_queue) // 快速匹配排队
+                .service(queue_status) // 排队状态
+                .service(v2::leave_queue) // 取消排队
+                .service(v2::join_seat_queue) // 排队等座位
+                .service(v2::leave_seat_queue) // 退出座位排队
+                .service(v2::choose_team) // 选择队伍
+                .service(v2::telephone_submit) // 传话模式提交
+                .service(v2::end_free_draw) // 结束协作画板
+                .service(v2::set_custom_words) // 上传自定义词库
+                .service(v2::reload_words) // 重新加载词库
+                .service(get_all_room) // 获取所有房间
+                .service(list_rooms) // 房间列表
+                .service(init) // 初始化
+                .service(client_poll) // 客户端轮训
+                .service(get_room_info) // 获取房间信息
+                .service(get_random_words) // 获取随机词库
                 .service(random_api) // 随机进房
-                
+                .service(canvas_png) // 画布导出 png
+                .service(canvas_svg) // 画布导出 svg
+                .service(round_thumbnail) // 回合缩略图
+                .service(get_gallery) // 本局画廊
+                .service(get_gallery_round) // 画廊回合详情
+                .service(get_replay) // 回合回放
+                .service(export_replay) // 导出回放文件
+                .service(import_replay) // 导入回放文件
+                .service(replay_gif) // 绘制过程动画 gif
+                .service(replay_apng) // 绘制过程动画 apng
+                .service(openapi_spec) // 接口文档
             )
     })
         .bind(format!("{}:{}", addr, port))
@@ -53,45 +140,75 @@ pub async fn run() -> std::io::Result<()> {
         .await
 }
 
+/// json 中一个坐标点最多占用的字节数，如橡皮擦的 `{"x":500,"y":500},`，留有空白的余量
+const JSON_BYTES_PER_POINT: usize = 32;
+/// v2 json 请求体的大小上限，足够容纳一笔点数最多的笔画
+const V2_JSON_LIMIT: usize = MAX_POINTS * JSON_BYTES_PER_POINT + 16 * 1024;
+
+fn v2_json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(V2_JSON_LIMIT)
+        .error_handler(json_error_handler)
+}
+
+/// json 解析失败时区分 请求体过大 / 类型不对 / 参数不合法
+fn json_error_handler(err: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+    let api_error = match err {
+        JsonPayloadError::Overflow => ApiError::PayloadTooLarge,
+        JsonPayloadError::ContentType => ApiError::UnsupportedMediaType,
+        _ => ApiError::InvalidPayload,
+    };
+    InternalError::from_response(err, api_error.error_response()).into()
+}
+
 lazy_static::lazy_static!{
     pub static ref ADDR: Addr<GameActor> = GameActor::default().start();
 }
 
 /// 创建房间
 #[get("/roomCreate")]
-async fn create_room() -> impl Responder {
-    ADDR.send(CreateRoomMsg{})
-        .await
-        .response(|id| {
-            info!("roomCreate: {}", id);
-            json!({
-                "id": id,
-            })
+async fn create_room(info: web::Query<CreateRoomMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .response(|room| {
+            info!("roomCreate: {}", room.id);
+            room
         })
 }
 
 /// 玩家加入房间
 #[post("/roomJoin")]
 async fn join_room(data: web::Json<JoinRoomMsg>) -> impl Responder {
-    info!("roomJoin: {:?}", data);
+    // 密码与邀请码不写入日志
+    info!("roomJoin: room {} user {}", data.room_id, data.player.user_id);
     ADDR.send(data.into_inner())
         .await
         .to_succ_response()
 }
 
+/// 管理员口令通过 `Authorization: Bearer <token>` 请求头传递，不出现在 url 中
+fn admin_token(req: &HttpRequest) -> Option<String> {
+    req.headers().get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
 #[get("/init")]
 async fn init(data: web::Query<InitMsg>) -> impl Responder {
     ADDR.send(data.into_inner())
         .await
-        .to_response()
+        .to_api_response()
 }
 
 #[get("/info/{id}")]
-async fn get_room_info(path: web::Path<String>) -> impl Responder {
-    let room_id = path.0;
-    ADDR.send(GetRoomInfoMsg{ id: room_id })
+async fn get_room_info(req: HttpRequest, path: web::Path<String>, info: web::Query<GetRoomInfoMsg>) -> impl Responder {
+    let mut msg = info.into_inner();
+    msg.id = path.0;
+    msg.admin_token = admin_token(&req);
+    ADDR.send(msg)
         .await
-        .to_response()
+        .to_api_response()
 }
 
 
@@ -100,11 +217,7 @@ async fn set_ready(info: web::Query<RoomReadyMsg>) -> impl Responder {
     info!("ready: {:?}", info);
     ADDR.send(info.into_inner())
         .await
-        .response(|_| {
-            json!({
-                "succ": true
-            })
-        })
+        .to_succ_response()
 }
 
 #[get("/getUp")]
@@ -126,7 +239,7 @@ async fn sit_on(info: web::Query<RoomSitOnMsg>) -> impl Responder {
 async fn client_poll(info: web::Query<PollingMsg>) -> impl Responder {
     ADDR.send(info.into_inner())
         .await
-        .to_response()
+        .to_api_response()
 }
 
 #[get("/chat")]
@@ -143,20 +256,27 @@ async fn get_all_room() -> impl Responder {
         .to_response()
 }
 
+#[get("/rooms")]
+/// 房间列表，支持过滤、排序和游标分页
+async fn list_rooms(info: web::Query<ListRoomsMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_api_response()
+}
+
 #[get("/leave")]
 async fn leave_room(info: web::Query<LeaveRoomMsg>) -> impl Responder {
     ADDR.send(info.into_inner())    
         .await
-        .response(|_| {
-            json!({
-                "succ": true
-            })
-        })
+        .to_succ_response()
 }
 
 #[get("/random")]
-async fn get_random_words(word: web::Data<WordEngine>) -> impl Responder {
-    HttpResponse::Ok().json(word.random(6))
+/// 带上 `userId` 时按所在房间的自定义词库出词
+async fn get_random_words(info: web::Query<RandomWordsMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_response()
 }
 
 #[get("/choose")]
@@ -171,7 +291,15 @@ async fn choose_a_word(info: web::Query<ChooseWordMsg>) -> impl Responder {
 async fn draw(info: web::Query<DrawMsg>) -> impl Responder {
     ADDR.send(info.into_inner())
         .await
-        .to_response()
+        .api_response(|_| true)
+}
+
+/// 图形绘制，数据结构较复杂，使用 json 提交
+#[post("/drawOp")]
+async fn draw_op(data: web::Json<DrawOpMsg>) -> impl Responder {
+    ADDR.send(data.into_inner())
+        .await
+        .to_succ_response()
 }
 
 #[get("/undo")]
@@ -192,13 +320,533 @@ async fn clear(info: web::Query<DrawClearMsg>) -> impl Responder {
 async fn set_color(info: web::Query<DrawChangeBackgoundMsg>) -> impl Responder {
     ADDR.send(info.into_inner())
         .await
-        .response(|_| true)
+        .api_response(|_| true)
+}
+
+#[get("/kick")]
+async fn kick_player(info: web::Query<KickPlayerMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/ban")]
+async fn ban_player(info: web::Query<BanPlayerMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/transferOwner")]
+async fn transfer_owner(info: web::Query<TransferOwnerMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/lockSeat")]
+async fn lock_seat(info: web::Query<LockSeatMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/settings")]
+async fn update_settings(info: web::Query<UpdateSettingsMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/forceStart")]
+async fn force_start(info: web::Query<ForceStartMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/vote")]
+async fn start_vote(info: web::Query<StartVoteMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/voteCast")]
+async fn cast_vote(info: web::Query<CastVoteMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/queue")]
+/// 加入快速匹配队列，凑够人后自动建房开局
+async fn join_queue(info: web::Query<JoinQueueMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_api_response()
+}
+
+#[get("/queue/status")]
+/// 查询排队状态，分配后返回房间id
+async fn queue_status(info: web::Query<QueueStatusMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_api_response()
+}
+
+#[get("/queue/leave")]
+async fn leave_queue(info: web::Query<LeaveQueueMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/seatQueue")]
+/// 座位满了时观战玩家排队，有空座位按顺序留座
+async fn join_seat_queue(info: web::Query<JoinSeatQueueMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/seatQueue/leave")]
+async fn leave_seat_queue(info: web::Query<LeaveSeatQueueMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/team")]
+async fn choose_team(info: web::Query<ChooseTeamMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/telephone/submit")]
+async fn telephone_submit(info: web::Query<TelephoneSubmitMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
+}
+
+#[get("/freeDraw/end")]
+async fn end_free_draw(info: web::Query<EndFreeDrawMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
+        .await
+        .to_succ_response()
 }
 
 #[get("/findJoinableRoom")]
-/// 查找能加入的房间，没有则返回失败
-async fn random_api() -> impl Responder {
-    ADDR.send(FindJoinableRoomMsg{})
+/// 匹配能加入的房间，带上玩家信息且没有合适的房间时新建
+async fn random_api(info: web::Query<FindJoinableRoomMsg>) -> impl Responder {
+    ADDR.send(info.into_inner())
         .await
-        .to_response()
+        .to_api_response()
+}
+
+
+/// 服务端渲染当前画布
+#[get("/room/{id}/canvas.png")]
+async fn canvas_png(path: web::Path<String>, info: web::Query<RenderCanvasMsg>) -> impl Responder {
+    let mut msg = info.into_inner();
+    msg.room_id = path.into_inner();
+    let (format, size) = (msg.format, msg.size);
+    render_canvas(msg, format, size, "image/png").await
+}
+
+/// 导出当前画布的矢量图
+#[get("/room/{id}/canvas.svg")]
+async fn canvas_svg(path: web::Path<String>, info: web::Query<RenderCanvasMsg>) -> impl Responder {
+    let mut msg = info.into_inner();
+    msg.room_id = path.into_inner();
+    msg.format = CanvasFormat::Svg;
+    let size = msg.size;
+    render_canvas(msg, CanvasFormat::Svg, size, "image/svg+xml").await
+}
+
+/// 渲染比较耗时，从 GameActor 复制出画布后放到线程池中执行，避免阻塞其他房间
+async fn render_canvas<M>(msg: M, format: CanvasFormat, size: u32, content_type: &str) -> HttpResponse
+where
+    M: Message<Result = ApiResult<CanvasSource>> + Send + 'static,
+    GameActor: Handler<M>,
+{
+    let source = match ADDR.send(msg).await {
+        Ok(Ok(source)) => source,
+        Ok(Err(e)) => return e.error_response(),
+        Err(e) => {
+            eprintln!("Encounter MailboxError: {}", e);
+            return ApiError::Internal.error_response()
+        }
+    };
+    match web::block(move || source.encode(format, size).ok_or(ApiError::Internal)).await {
+        Ok(bytes) => HttpResponse::Ok().content_type(content_type).body(bytes),
+        Err(_) => ApiError::Internal.error_response(),
+    }
+}
+
+/// 本局某一回合结束时的缩略图
+#[get("/room/{id}/thumbnail/{round}")]
+async fn round_thumbnail(path: web::Path<(String, usize)>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    render_canvas(RoundThumbnailMsg { room_id, round }, CanvasFormat::Png, THUMBNAIL_SIZE, "image/png").await
+}
+
+/// 本局所有回合的概要
+#[get("/room/{id}/gallery")]
+async fn get_gallery(path: web::Path<String>) -> impl Responder {
+    ADDR.send(GetGalleryMsg { room_id: path.into_inner() })
+        .await
+        .to_api_response()
+}
+
+/// 某一回合的详细数据，包括笔画
+#[get("/room/{id}/gallery/{round}")]
+async fn get_gallery_round(path: web::Path<(String, usize)>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    ADDR.send(GetGalleryRoundMsg { room_id, round })
+        .await
+        .to_api_response()
+}
+
+/// 回合回放：笔画、聊天与得分在同一时间轴上
+#[get("/room/{id}/gallery/{round}/replay")]
+async fn get_replay(path: web::Path<(String, usize)>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    ADDR.send(GetReplayMsg { room_id, round })
+        .await
+        .to_api_response()
+}
+
+/// 以文件形式下载回放，可离线导入
+#[get("/room/{id}/gallery/{round}/replay.json")]
+async fn export_replay(path: web::Path<(String, usize)>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    match ADDR.send(GetReplayMsg { room_id, round }).await {
+        Ok(Ok(replay)) => HttpResponse::Ok()
+            .header("Content-Disposition", format!("attachment; filename=\"replay-{}.json\"", round))
+            .json(replay),
+        Ok(Err(e)) => e.error_response(),
+        Err(e) => {
+            eprintln!("Encounter MailboxError: {}", e);
+            ApiError::Internal.error_response()
+        }
+    }
+}
+
+/// 导入回放文件，校验后返回规范化的回放数据
+#[post("/replay/import")]
+async fn import_replay(body: String) -> impl Responder {
+    match Replay::import(&body) {
+        Ok(replay) => HttpResponse::Ok().json(replay),
+        Err(reason) => {
+            info!("import replay failed: {}", reason);
+            ApiError::InvalidPayload.error_response_with_reason(&reason)
+        },
+    }
+}
+
+/// 绘制过程的 gif 动画
+#[get("/room/{id}/gallery/{round}/animation.gif")]
+async fn replay_gif(path: web::Path<(String, usize)>, info: web::Query<AnimationOptions>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    let mut options = info.into_inner();
+    options.format = AnimationFormat::Gif;
+    replay_animation(room_id, round, options, "image/gif").await
+}
+
+/// 绘制过程的 apng 动画
+#[get("/room/{id}/gallery/{round}/animation.png")]
+async fn replay_apng(path: web::Path<(String, usize)>, info: web::Query<AnimationOptions>) -> impl Responder {
+    let (room_id, round) = path.into_inner();
+    let mut options = info.into_inner();
+    options.format = AnimationFormat::Apng;
+    replay_animation(room_id, round, options, "image/apng").await
+}
+
+/// 编码比较耗时，放到线程池中执行，避免阻塞 GameActor
+async fn replay_animation(room_id: String, round: usize, options: AnimationOptions, content_type: &str) -> HttpResponse {
+    let replay = match ADDR.send(GetReplayMsg { room_id, round }).await {
+        Ok(Ok(replay)) => replay,
+        Ok(Err(e)) => return e.error_response(),
+        Err(e) => {
+            eprintln!("Encounter MailboxError: {}", e);
+            return ApiError::Internal.error_response()
+        }
+    };
+    match web::block(move || animation::encode(&replay, &options).ok_or(ApiError::Internal)).await {
+        Ok(bytes) => HttpResponse::Ok().content_type(content_type).body(bytes),
+        Err(_) => ApiError::Internal.error_response(),
+    }
+}
+
+/// 重新加载词库，读取文件放到线程池中执行；新文件无效时继续使用原有词库
+async fn reload_word_packs(req: &HttpRequest) -> HttpResponse {
+    if !snapshot::is_admin(admin_token(req).as_deref()) {
+        return ApiError::NotAdmin.error_response()
+    }
+    match web::block(word::reload).await {
+        Ok(packs) => HttpResponse::Ok().json(ReloadWordsResult { packs: packs.summary() }),
+        Err(e) => {
+            info!("reload word packs failed: {}", e);
+            ApiError::InvalidWordPack.error_response()
+        },
+    }
+}
+
+/// OpenAPI 3 接口文档
+#[get("/openapi.json")]
+async fn openapi_spec() -> impl Responder {
+    HttpResponse::Ok().json(&*openapi::SPEC)
+}
+
+/// v2 接口：修改类操作统一使用 POST json，返回 `{ succ: true }` 或错误结构
+mod v2 {
+    use actix_web::{HttpRequest, Responder, post, web};
+
+    use super::ADDR;
+    use crate::{message::*, wrap::{WarpSuccResponse, WrapApiResponse, WrapMailboxErrorResponse}};
+
+    /// 请求体可以省略，省略时创建公开房间
+    #[post("/roomCreate")]
+    pub async fn create_room(data: Option<web::Json<CreateRoomMsg>>) -> impl Responder {
+        ADDR.send(data.map(|data| data.into_inner()).unwrap_or_default())
+            .await
+            .to_response()
+    }
+
+    #[post("/leave")]
+    pub async fn leave_room(data: web::Json<LeaveRoomMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/ready")]
+    pub async fn set_ready(data: web::Json<RoomReadyMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/getUp")]
+    pub async fn get_up(data: web::Json<RoomGetUpMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/sitOn")]
+    pub async fn sit_on(data: web::Json<RoomSitOnMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/chat")]
+    pub async fn chat(data: web::Json<ChatMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/choose")]
+    pub async fn choose_a_word(data: web::Json<ChooseWordMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/draw")]
+    pub async fn draw(data: web::Json<DrawMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/undo")]
+    pub async fn undo(data: web::Json<DrawUndoMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/clear")]
+    pub async fn clear(data: web::Json<DrawClearMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/setColor")]
+    pub async fn set_color(data: web::Json<DrawChangeBackgoundMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/kick")]
+    pub async fn kick_player(data: web::Json<KickPlayerMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/ban")]
+    pub async fn ban_player(data: web::Json<BanPlayerMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/transferOwner")]
+    pub async fn transfer_owner(data: web::Json<TransferOwnerMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/lockSeat")]
+    pub async fn lock_seat(data: web::Json<LockSeatMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/settings")]
+    pub async fn update_settings(data: web::Json<UpdateSettingsMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/forceStart")]
+    pub async fn force_start(data: web::Json<ForceStartMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/vote")]
+    pub async fn start_vote(data: web::Json<StartVoteMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/voteCast")]
+    pub async fn cast_vote(data: web::Json<CastVoteMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/queue")]
+    pub async fn join_queue(data: web::Json<JoinQueueMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_api_response()
+    }
+
+    #[post("/queue/leave")]
+    pub async fn leave_queue(data: web::Json<LeaveQueueMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/seatQueue")]
+    pub async fn join_seat_queue(data: web::Json<JoinSeatQueueMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/seatQueue/leave")]
+    pub async fn leave_seat_queue(data: web::Json<LeaveSeatQueueMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/team")]
+    pub async fn choose_team(data: web::Json<ChooseTeamMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/telephone/submit")]
+    pub async fn telephone_submit(data: web::Json<TelephoneSubmitMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/freeDraw/end")]
+    pub async fn end_free_draw(data: web::Json<EndFreeDrawMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_succ_response()
+    }
+
+    #[post("/customWords")]
+    pub async fn set_custom_words(data: web::Json<SetCustomWordsMsg>) -> impl Responder {
+        ADDR.send(data.into_inner())
+            .await
+            .to_api_response()
+    }
+
+    #[post("/admin/reloadWords")]
+    pub async fn reload_words(req: HttpRequest) -> impl Responder {
+        super::reload_word_packs(&req).await
+    }
+}
+
+#[test]
+fn test_v2_json_limit() {
+    use actix_web::{http::StatusCode, rt::System, test};
+    use crate::draw_data::{DrawOp, Point, CANVAS_SIZE, MAX_LINE_WIDTH};
+
+    // 点数最多的橡皮擦笔画不超过上限
+    let eraser = DrawOp::Eraser {
+        points: vec![Point { x: CANVAS_SIZE, y: CANVAS_SIZE }; MAX_POINTS],
+        width: MAX_LINE_WIDTH,
+    };
+    let body = serde_json::json!({
+        "userId": "00000000-0000-0000-0000-000000000000",
+        "op": eraser,
+        "timestamp": i64::MAX,
+    }).to_string();
+    assert!(body.len() <= V2_JSON_LIMIT);
+
+    System::new("test").block_on(async {
+        let mut app = test::init_service(
+            App::new().service(web::scope("/v2").app_data(v2_json_config()).service(draw_op))
+        ).await;
+        let post = |content_type: &str, body: String| test::TestRequest::post()
+            .uri("/v2/drawOp")
+            .header(CONTENT_TYPE, content_type)
+            .set_payload(body)
+            .to_request();
+        let oversized = format!("{{\"userId\":\"{}\"}}", "a".repeat(V2_JSON_LIMIT));
+        let resp = test::call_service(&mut app, post("application/json", oversized)).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let resp = test::call_service(&mut app, post("text/plain", body)).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let resp = test::call_service(&mut app, post("application/json", "{".to_string())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    });
+}
+
+#[test]
+fn test_admin_token() {
+    use actix_web::test::TestRequest;
+
+    let req = TestRequest::default().header(AUTHORIZATION, "Bearer secret").to_http_request();
+    assert_eq!(admin_token(&req).as_deref(), Some("secret"));
+    let req = TestRequest::default().header(AUTHORIZATION, "secret").to_http_request();
+    assert_eq!(admin_token(&req), None);
+    assert_eq!(admin_token(&TestRequest::default().to_http_request()), None);
 }
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use rand::prelude::SliceRandom;
+
+use crate::{error::{ApiError, ApiResult}, room::{GameStage, RoomData}};
+
+/// 自定义词库最多的词数
+pub const MAX_CUSTOM_WORDS: usize = 200;
+/// 自定义词的最大长度（字符）
+pub const MAX_CUSTOM_WORD_LEN: usize = 12;
+/// 默认候选词中自定义词的比例（百分比）
+pub const CUSTOM_WORD_RATIO: u8 = 100;
+
+/// 零宽字符等看不见的字符
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}')
+}
+
+/// 去掉控制字符与看不见的字符，连续空白合并为一个空格
+pub fn sanitize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| !c.is_control() || c.is_whitespace())
+        .filter(|c| !is_invisible(*c))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 解析上传的词，每行一个或以逗号分隔，忽略空行并去重
+pub fn parse_custom_words(raw: &str) -> ApiResult<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut words = vec![];
+    for word in raw.split(|c| c == '\n' || c == ',' || c == '，').map(sanitize_word) {
+        if word.is_empty() {
+            continue
+        }
+        if word.chars().count() > MAX_CUSTOM_WORD_LEN {
+            return Err(ApiError::InvalidPayload)
+        }
+        if seen.insert(word.to_lowercase()) {
+            words.push(word);
+        }
+    }
+    if words.len() > MAX_CUSTOM_WORDS {
+        return Err(ApiError::InvalidPayload)
+    }
+    Ok(words)
+}
+
+impl RoomData {
+    /// 房主在准备阶段上传自定义词库，空列表表示不再使用；返回去重后的词数
+    pub fn set_custom_words(&mut self, user_id: &String, raw: &str) -> ApiResult<usize> {
+        self.check_owner(user_id)?;
+        if self.stage != GameStage::Ready {
+            return Err(ApiError::WrongStage)
+        }
+        self.custom_words = parse_custom_words(raw)?;
+        Ok(self.custom_words.len())
+    }
+
+    /// 按设置的比例用自定义词替换全局词库抽出的候选词，自定义词不够时用全局词补齐
+    pub fn offer_words(&self, global: Vec<String>) -> Vec<String> {
+        if self.custom_words.is_empty() {
+            return global
+        }
+        let amount = global.len();
+        let custom = (amount * self.settings.custom_word_ratio as usize + 50) / 100;
+        let mut rng = rand::thread_rng();
+        let mut words = self.custom_words
+            .choose_multiple(&mut rng, custom)
+            .cloned()
+            .collect::<Vec<_>>();
+        for word in global {
+            if words.len() >= amount {
+                break
+            }
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        words.shuffle(&mut rng);
+        words
+    }
+}
+
+#[test]
+fn test_custom_words() {
+    use crate::room::SettingsPatch;
+
+    assert_eq!(sanitize_word("  猫\u{200b}咪 \t 大战\u{7}  "), "猫咪 大战");
+    assert_eq!(parse_custom_words("苹果\n\n香蕉,苹果，Apple, apple ").unwrap(), vec!["苹果", "香蕉", "Apple"]);
+    assert_eq!(parse_custom_words("这是一个非常非常非常长的词语"), Err(ApiError::InvalidPayload));
+    let too_many = (0..=MAX_CUSTOM_WORDS).map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+    assert_eq!(parse_custom_words(&too_many), Err(ApiError::InvalidPayload));
+
+    let mut room = RoomData::with_players(0..2);
+    let id = |id: &str| id.to_string();
+    let global = || ["a", "b", "c", "d", "e", "f"].iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    assert_eq!(room.offer_words(global()), global());
+    assert_eq!(room.set_custom_words(&id("1"), "苹果"), Err(ApiError::NotOwner));
+    assert_eq!(room.set_custom_words(&id("0"), "苹果\n香蕉\n西瓜\n葡萄"), Ok(4));
+
+    // 自定义词不够时用全局词补齐
+    let words = room.offer_words(global());
+    assert_eq!(words.len(), 6);
+    assert_eq!(words.iter().filter(|w| room.custom_words.contains(w)).count(), 4);
+
+    room.update_settings(&id("0"), SettingsPatch { custom_word_ratio: Some(50), ..Default::default() }).unwrap();
+    let words = room.offer_words(global());
+    assert_eq!(words.iter().filter(|w| room.custom_words.contains(w)).count(), 3);
+
+    room.stage = GameStage::Drawing;
+    assert_eq!(room.set_custom_words(&id("0"), ""), Err(ApiError::WrongStage));
+}
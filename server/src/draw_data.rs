@@ -1,22 +1,182 @@
-use std::vec;
+use std::{fmt::Write, vec};
 
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::render::Canvas;
+
+/// 画布尺寸（与前端 canvas 宽高一致）
+pub const CANVAS_SIZE: i32 = 500;
+/// 线宽上限
+pub const MAX_LINE_WIDTH: u16 = 50;
+/// 单个笔画最多的点数
+pub const MAX_POINTS: usize = 4096;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DrawData {
     inner: Vec<DrawDataUnit>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DrawDataUnit {
-    pub data: String,
+    #[serde(flatten)]
+    pub op: DrawOp,
     pub timestamp: i64,
+    /// 协作画板模式下笔画的作者
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+/// 坐标点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// 绘制操作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DrawOp {
+    /// 自由笔画，格式为 `线宽;颜色;时长|坐标字符串`
+    Path { data: String },
+    /// 直线
+    Line { from: Point, to: Point, color: String, width: u16 },
+    /// 矩形，`from` 与 `to` 为对角
+    Rect { from: Point, to: Point, color: String, width: u16, fill: Option<String> },
+    /// 椭圆，`from` 与 `to` 为外接矩形的对角
+    Ellipse { from: Point, to: Point, color: String, width: u16, fill: Option<String> },
+    /// 在某点进行油漆桶填充
+    Fill { at: Point, color: String },
+    /// 橡皮擦笔画
+    Eraser { points: Vec<Point>, width: u16 },
+}
+
+/// 解析后的自由笔画
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathStroke {
+    pub width: u16,
+    pub color: String,
+    /// 绘制时长（秒）
+    pub duration: f64,
+    pub points: Vec<Point>,
+}
+
+impl PathStroke {
+    /// 解析前端上传的笔画字符串，坐标以字符编码的形式两两存放
+    pub fn parse(data: &str) -> Option<Self> {
+        let (header, body) = data.split_at(data.find('|')?);
+        let mut meta = header.split(';');
+        let width = meta.next()?.trim().parse::<f64>().ok()?;
+        let color = meta.next()?.trim().to_string();
+        let duration = meta.next().and_then(|d| d.trim().parse::<f64>().ok()).unwrap_or(1.0);
+        let codes = body[1..].encode_utf16().map(|c| c as i32).collect::<Vec<_>>();
+        let points = codes.chunks_exact(2)
+            .map(|c| Point { x: c[0], y: c[1] })
+            .collect();
+        Some(Self {
+            width: width.round() as u16,
+            color,
+            duration,
+            points,
+        })
+    }
+}
+
+impl DrawOp {
+    /// 校验绘制操作是否合法
+    pub fn validate(&self) -> bool {
+        match self {
+            DrawOp::Path { data } => {
+                match PathStroke::parse(data) {
+                    Some(stroke) => valid_width(stroke.width)
+                        && is_valid_color(&stroke.color)
+                        && stroke.points.len() <= MAX_POINTS
+                        && stroke.points.iter().all(in_canvas),
+                    None => false,
+                }
+            },
+            DrawOp::Line { from, to, color, width } => {
+                in_canvas(from) && in_canvas(to) && is_valid_color(color) && valid_width(*width)
+            },
+            DrawOp::Rect { from, to, color, width, fill }
+            | DrawOp::Ellipse { from, to, color, width, fill } => {
+                in_canvas(from) && in_canvas(to)
+                    && is_valid_color(color)
+                    && valid_width(*width)
+                    && fill.as_deref().map_or(true, is_valid_color)
+            },
+            DrawOp::Fill { at, color } => in_canvas(at) && is_valid_color(color),
+            DrawOp::Eraser { points, width } => {
+                !points.is_empty()
+                    && points.len() <= MAX_POINTS
+                    && points.iter().all(in_canvas)
+                    && valid_width(*width)
+            },
+        }
+    }
+}
+
+fn in_canvas(p: &Point) -> bool {
+    (0..=CANVAS_SIZE).contains(&p.x) && (0..=CANVAS_SIZE).contains(&p.y)
+}
+
+fn valid_width(width: u16) -> bool {
+    width > 0 && width <= MAX_LINE_WIDTH
+}
+
+/// 是否为 `#rgb` 或 `#rrggbb` 格式的颜色
+pub fn is_valid_color(color: &str) -> bool {
+    parse_color(color).is_some()
+}
+
+/// 解析 `#rgb` 或 `#rrggbb` 格式的颜色
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+    match hex.len() {
+        3 => {
+            let mut rgb = [0u8; 3];
+            for (i, c) in hex.chars().enumerate() {
+                let v = c.to_digit(16)? as u8;
+                rgb[i] = v * 17;
+            }
+            Some(rgb)
+        },
+        6 => {
+            let mut rgb = [0u8; 3];
+            for i in 0..3 {
+                rgb[i] = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+            }
+            Some(rgb)
+        },
+        _ => None,
+    }
+}
+
+impl From<Vec<DrawDataUnit>> for DrawData {
+    fn from(inner: Vec<DrawDataUnit>) -> Self {
+        Self { inner }
+    }
 }
 
 impl DrawData {
-    pub fn draw(&mut self, data: String, timestamp: i64) {
-        let new_data = DrawDataUnit { data, timestamp };
-        self.inner.push(new_data);
+    /// 添加绘制操作，不合法的操作会被丢弃
+    pub fn draw_op(&mut self, op: DrawOp, timestamp: i64) -> bool {
+        self.push(op, timestamp, None)
+    }
+    /// 添加绘制操作并记录作者
+    pub fn draw_op_by(&mut self, op: DrawOp, timestamp: i64, author: &str) -> bool {
+        self.push(op, timestamp, Some(author.to_string()))
+    }
+    fn push(&mut self, op: DrawOp, timestamp: i64, author: Option<String>) -> bool {
+        if !op.validate() {
+            return false
+        }
+        self.inner.push(DrawDataUnit { op, timestamp, author });
+        true
     }
     pub fn slice(&self, index: usize) -> &[DrawDataUnit] {
         if let Some(len) = self.inner.len().checked_sub(index) {
@@ -32,9 +192,111 @@ impl DrawData {
     pub fn undo(&mut self) {
         self.inner.pop();
     }
+    /// 撤销作者自己的最后一笔，返回是否撤销了
+    pub fn undo_by(&mut self, author: &str) -> bool {
+        match self.inner.iter().rposition(|unit| unit.author.as_deref() == Some(author)) {
+            Some(index) => {
+                self.inner.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+    /// 清除作者自己的全部笔画，返回是否清除了
+    pub fn clear_by(&mut self, author: &str) -> bool {
+        let len = self.inner.len();
+        self.inner.retain(|unit| unit.author.as_deref() != Some(author));
+        self.inner.len() != len
+    }
     pub fn len(&self) -> usize {
         self.inner.len()
     }
+    /// 导出为独立的 svg 文件
+    ///
+    /// 撤销与清空会直接移除笔画，因此这里只会输出当前可见的笔画。
+    /// 油漆桶没有对应的矢量图形，按画布像素计算填充区域后以路径输出。
+    pub fn to_svg(&self, background: &str, size: u32) -> String {
+        let background = svg_color(background).unwrap_or_else(|| "#ffffff".to_string());
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {canvas} {canvas}">"#,
+            size = size,
+            canvas = CANVAS_SIZE,
+        );
+        let _ = write!(svg, r#"<rect width="100%" height="100%" fill="{}"/>"#, background);
+        // 用于计算油漆桶的填充区域
+        let mut canvas = Canvas::new(CANVAS_SIZE as u32, &background);
+        for unit in self.inner.iter() {
+            let element = svg_element(&unit.op, &canvas, &background);
+            canvas.apply(&unit.op);
+            if let Some(element) = element {
+                svg.push_str(&element);
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// 单个绘制操作对应的 svg 元素，`canvas` 为该操作之前的画布状态
+fn svg_element(op: &DrawOp, canvas: &Canvas, background: &str) -> Option<String> {
+    let element = match op {
+        DrawOp::Path { data } => {
+            let stroke = PathStroke::parse(data)?;
+            svg_polyline(&stroke.points, &stroke.color, stroke.width)?
+        },
+        DrawOp::Line { from, to, color, width } => svg_polyline(&[*from, *to], color, *width)?,
+        DrawOp::Rect { from, to, color, width, fill } => format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" stroke-linejoin="round"/>"#,
+            from.x.min(to.x), from.y.min(to.y),
+            (to.x - from.x).abs(), (to.y - from.y).abs(),
+            fill.as_deref().and_then(svg_color).unwrap_or_else(|| "none".to_string()),
+            svg_color(color)?, width,
+        ),
+        DrawOp::Ellipse { from, to, color, width, fill } => format!(
+            r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+            (from.x + to.x) as f64 / 2.0, (from.y + to.y) as f64 / 2.0,
+            (to.x - from.x).abs() as f64 / 2.0, (to.y - from.y).abs() as f64 / 2.0,
+            fill.as_deref().and_then(svg_color).unwrap_or_else(|| "none".to_string()),
+            svg_color(color)?, width,
+        ),
+        DrawOp::Fill { at, color } => {
+            let mut d = String::new();
+            for (y, x, len) in canvas.fill_runs(at) {
+                let _ = write!(d, "M{} {}h{}v1h-{}z", x, y, len, len);
+            }
+            format!(r#"<path d="{}" fill="{}"/>"#, d, svg_color(color)?)
+        },
+        DrawOp::Eraser { points, width } => svg_polyline(points, background, *width)?,
+    };
+    Some(element)
+}
+
+/// 规范化为 `#rrggbb`，避免把任意字符串写进 svg
+fn svg_color(color: &str) -> Option<String> {
+    parse_color(color).map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+fn svg_polyline(points: &[Point], color: &str, width: u16) -> Option<String> {
+    let color = svg_color(color)?;
+    match points {
+        [] => None,
+        [p] => Some(format!(
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+            p.x, p.y, width as f64 / 2.0, color,
+        )),
+        _ => {
+            let points = points.iter()
+                .map(|p| format!("{},{}", p.x, p.y))
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(format!(
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                points, color, width,
+            ))
+        },
+    }
 }
 
 impl Default for DrawData {
@@ -52,13 +314,71 @@ fn test_slice() {
     // 3, 2 => 0
     // 2, 4 => 2
     let mut dd = DrawData::default();
-    dd.draw("test".into(), 1);
-    dd.draw("test".into(), 1);
+    let path = || DrawOp::Path { data: "2;#000000;1|".into() };
+    dd.draw_op(path(), 1);
+    dd.draw_op(path(), 1);
     assert_eq!(dd.slice(0).len(), 2);
     assert_eq!(dd.slice(2).len(), 0);
     assert_eq!(dd.slice(3).len(), 0);
-    dd.draw("test".into(), 1);
-    dd.draw("test".into(), 1);
+    dd.draw_op(path(), 1);
+    dd.draw_op(path(), 1);
     assert_eq!(dd.slice(2).len(), 2);
 }
 
+#[test]
+fn test_draw_op() {
+    let mut dd = DrawData::default();
+    let path = |data: &str| DrawOp::Path { data: data.into() };
+    assert!(!dd.draw_op(path("test"), 1));
+    assert!(dd.draw_op(path("4;#ff0000;1|\u{a}\u{14}\u{1e}\u{28}"), 1));
+    // 坐标超出画布
+    assert!(!dd.draw_op(path("4;#ff0000;1|\u{a}\u{14}\u{1e}\u{258}"), 1));
+    assert!(dd.draw_op(DrawOp::Rect {
+        from: Point { x: 10, y: 10 },
+        to: Point { x: 100, y: 80 },
+        color: "#000".into(),
+        width: 2,
+        fill: Some("#00ff00".into()),
+    }, 2));
+    assert!(!dd.draw_op(DrawOp::Fill { at: Point { x: 600, y: 0 }, color: "#000".into() }, 3));
+    assert!(!dd.draw_op(DrawOp::Line {
+        from: Point { x: 0, y: 0 },
+        to: Point { x: 1, y: 1 },
+        color: "red".into(),
+        width: 2,
+    }, 3));
+    assert_eq!(dd.len(), 2);
+
+    let value = serde_json::to_value(dd.slice(0)).unwrap();
+    assert_eq!(value[0]["kind"], "path");
+    assert_eq!(value[1]["kind"], "rect");
+    assert_eq!(value[1]["timestamp"], 2);
+
+    let stroke = PathStroke::parse("4;#ff0000;1|\u{a}\u{14}\u{1e}\u{28}").unwrap();
+    assert_eq!(stroke.points, vec![Point { x: 10, y: 20 }, Point { x: 30, y: 40 }]);
+}
+
+#[test]
+fn test_svg() {
+    let mut dd = DrawData::default();
+    dd.draw_op(DrawOp::Line {
+        from: Point { x: 0, y: 0 },
+        to: Point { x: 10, y: 10 },
+        color: "#f00".into(),
+        width: 2,
+    }, 1);
+    dd.draw_op(DrawOp::Ellipse {
+        from: Point { x: 10, y: 10 },
+        to: Point { x: 30, y: 50 },
+        color: "#000000".into(),
+        width: 2,
+        fill: None,
+    }, 2);
+    let svg = dd.to_svg("#ffffff", 500);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains(r##"stroke="#ff0000""##));
+    assert!(svg.contains("<ellipse"));
+    dd.undo();
+    let svg = dd.to_svg("#ffffff", 500);
+    assert!(!svg.contains("<ellipse"));
+}
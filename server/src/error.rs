@@ -0,0 +1,195 @@
+use std::fmt;
+
+use actix_web::http::StatusCode;
+
+/// 接口错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiError {
+    /// 房间不存在
+    RoomNotFound,
+    /// 回合不存在
+    RoundNotFound,
+    /// 没有可加入的房间
+    NoJoinableRoom,
+    /// 玩家不在房间内
+    NotInRoom,
+    /// 玩家已经在房间内
+    AlreadyInRoom,
+    /// 不是当前回合的玩家
+    NotYourTurn,
+    /// 当前游戏阶段不允许该操作
+    WrongStage,
+    /// 座位已经有人
+    SeatTaken,
+    /// 玩家不在座位上
+    NotSeated,
+    /// 玩家不在观战列表中
+    NotObserver,
+    /// 私密房间的密码或邀请码不正确
+    WrongPassword,
+    /// 只有房主能进行的操作
+    NotOwner,
+    /// 被房主封禁
+    Banned,
+    /// 座位已被房主锁定
+    SeatLocked,
+    /// 人数不足，无法开始
+    NotEnoughPlayers,
+    /// 已有投票正在进行
+    VoteInProgress,
+    /// 当前没有投票
+    NoActiveVote,
+    /// 发起投票过于频繁
+    VoteCooldown,
+    /// 观战人数已满
+    ObserversFull,
+    /// 房间不允许观战玩家发言
+    ObserverChatDisabled,
+    /// 需要管理员口令
+    NotAdmin,
+    /// 词库文件无效，继续使用原有词库
+    InvalidWordPack,
+    /// 请求参数不合法
+    InvalidPayload,
+    /// 请求体过大
+    PayloadTooLarge,
+    /// 请求体不是 json
+    UnsupportedMediaType,
+    /// 服务器内部错误
+    Internal,
+}
+
+impl ApiError {
+    /// 全部错误，用于生成接口文档
+    pub const ALL: [ApiError; 26] = [
+        ApiError::RoomNotFound,
+        ApiError::RoundNotFound,
+        ApiError::NoJoinableRoom,
+        ApiError::NotInRoom,
+        ApiError::AlreadyInRoom,
+        ApiError::NotYourTurn,
+        ApiError::WrongStage,
+        ApiError::SeatTaken,
+        ApiError::NotSeated,
+        ApiError::NotObserver,
+        ApiError::WrongPassword,
+        ApiError::NotOwner,
+        ApiError::Banned,
+        ApiError::SeatLocked,
+        ApiError::NotEnoughPlayers,
+        ApiError::VoteInProgress,
+        ApiError::NoActiveVote,
+        ApiError::VoteCooldown,
+        ApiError::ObserversFull,
+        ApiError::ObserverChatDisabled,
+        ApiError::NotAdmin,
+        ApiError::InvalidWordPack,
+        ApiError::InvalidPayload,
+        ApiError::PayloadTooLarge,
+        ApiError::UnsupportedMediaType,
+        ApiError::Internal,
+    ];
+
+    /// 稳定的错误码，供客户端判断
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::RoomNotFound => "ROOM_NOT_FOUND",
+            ApiError::RoundNotFound => "ROUND_NOT_FOUND",
+            ApiError::NoJoinableRoom => "NO_JOINABLE_ROOM",
+            ApiError::NotInRoom => "NOT_IN_ROOM",
+            ApiError::AlreadyInRoom => "ALREADY_IN_ROOM",
+            ApiError::NotYourTurn => "NOT_YOUR_TURN",
+            ApiError::WrongStage => "WRONG_STAGE",
+            ApiError::SeatTaken => "SEAT_TAKEN",
+            ApiError::NotSeated => "NOT_SEATED",
+            ApiError::NotObserver => "NOT_OBSERVER",
+            ApiError::WrongPassword => "WRONG_PASSWORD",
+            ApiError::NotOwner => "NOT_OWNER",
+            ApiError::Banned => "BANNED",
+            ApiError::SeatLocked => "SEAT_LOCKED",
+            ApiError::NotEnoughPlayers => "NOT_ENOUGH_PLAYERS",
+            ApiError::VoteInProgress => "VOTE_IN_PROGRESS",
+            ApiError::NoActiveVote => "NO_ACTIVE_VOTE",
+            ApiError::VoteCooldown => "VOTE_COOLDOWN",
+            ApiError::ObserversFull => "OBSERVERS_FULL",
+            ApiError::ObserverChatDisabled => "OBSERVER_CHAT_DISABLED",
+            ApiError::NotAdmin => "NOT_ADMIN",
+            ApiError::InvalidWordPack => "INVALID_WORD_PACK",
+            ApiError::InvalidPayload => "INVALID_PAYLOAD",
+            ApiError::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ApiError::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            ApiError::Internal => "INTERNAL",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::RoomNotFound
+            | ApiError::RoundNotFound
+            | ApiError::NoJoinableRoom => StatusCode::NOT_FOUND,
+            ApiError::NotInRoom
+            | ApiError::NotYourTurn
+            | ApiError::WrongPassword
+            | ApiError::NotOwner
+            | ApiError::Banned
+            | ApiError::ObserverChatDisabled
+            | ApiError::NotAdmin => StatusCode::FORBIDDEN,
+            ApiError::WrongStage
+            | ApiError::AlreadyInRoom
+            | ApiError::SeatTaken
+            | ApiError::NotSeated
+            | ApiError::NotObserver
+            | ApiError::SeatLocked
+            | ApiError::NotEnoughPlayers
+            | ApiError::VoteInProgress
+            | ApiError::NoActiveVote
+            | ApiError::ObserversFull => StatusCode::CONFLICT,
+            ApiError::VoteCooldown => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::InvalidPayload => StatusCode::BAD_REQUEST,
+            ApiError::InvalidWordPack => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// 给人看的错误信息
+    pub fn message(&self) -> &'static str {
+        match self {
+            ApiError::RoomNotFound => "房间不存在",
+            ApiError::RoundNotFound => "回合不存在",
+            ApiError::NoJoinableRoom => "没有可加入的房间",
+            ApiError::NotInRoom => "玩家不在房间内",
+            ApiError::AlreadyInRoom => "已经在房间内，请先离开房间",
+            ApiError::NotYourTurn => "还没轮到你",
+            ApiError::WrongStage => "当前阶段不能进行该操作",
+            ApiError::SeatTaken => "座位已经有人了",
+            ApiError::NotSeated => "玩家不在座位上",
+            ApiError::NotObserver => "玩家不在观战列表中",
+            ApiError::WrongPassword => "房间密码或邀请码错误",
+            ApiError::NotOwner => "只有房主可以进行该操作",
+            ApiError::Banned => "你已被房主移出该房间",
+            ApiError::SeatLocked => "座位已被锁定",
+            ApiError::NotEnoughPlayers => "人数不足，无法开始",
+            ApiError::VoteInProgress => "已有投票正在进行",
+            ApiError::NoActiveVote => "当前没有投票",
+            ApiError::VoteCooldown => "发起投票过于频繁，请稍后再试",
+            ApiError::ObserversFull => "观战人数已满",
+            ApiError::ObserverChatDisabled => "该房间不允许观战玩家发言",
+            ApiError::NotAdmin => "需要管理员权限",
+            ApiError::InvalidWordPack => "词库文件无效，仍在使用原有词库",
+            ApiError::InvalidPayload => "请求参数不合法",
+            ApiError::PayloadTooLarge => "请求数据过大",
+            ApiError::UnsupportedMediaType => "请求数据必须为 json",
+            ApiError::Internal => "服务器内部错误",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
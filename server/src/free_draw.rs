@@ -0,0 +1,135 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{
+    draw_data::{DrawDataUnit, DrawOp},
+    error::{ApiError, ApiResult},
+    room::{GameStage, RoomData, DEFAULT_COLOR},
+};
+
+/// 协作画板最多的笔画数，画满后需要撤销或清除才能继续画
+pub const MAX_FREE_DRAW_UNITS: usize = 2000;
+
+/// 协作画板阶段：新增的笔画，每一笔带有作者
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeDrawSnapshot {
+    pub draw_list: Vec<DrawDataUnit>,
+    pub background: String,
+    /// 中间的笔画被撤销或清除时递增，客户端发现变化后应以 `drawIndex=0` 重新拉取
+    pub revision: u32,
+}
+
+impl RoomData {
+    /// 开始协作画板，没有词汇、计时与计分
+    pub fn start_free_draw(&mut self) {
+        self.reset_ready();
+        self.draw_data.clear();
+        self.draw_revision += 1;
+        self.background = DEFAULT_COLOR.to_string();
+        self.word.clear();
+        self.cur_seat = None;
+        self.cur_id = None;
+        self.next_timestamp = None;
+        self.stage = GameStage::FreeDraw;
+    }
+
+    /// 房主结束协作画板，回到准备阶段
+    pub fn end_free_draw(&mut self, user_id: &String) -> ApiResult<()> {
+        self.check_owner(user_id)?;
+        if self.stage != GameStage::FreeDraw {
+            return Err(ApiError::WrongStage)
+        }
+        self.stage = GameStage::Ready;
+        self.draw_data.clear();
+        self.background = DEFAULT_COLOR.to_string();
+        self.next_timestamp = Some(0);
+        Ok(())
+    }
+
+    /// 座位上的玩家都可以画
+    pub(crate) fn check_free_drawer(&self, user_id: &String) -> ApiResult<()> {
+        if self.seat.iter().flatten().any(|id| id == user_id) {
+            Ok(())
+        } else {
+            Err(ApiError::NotSeated)
+        }
+    }
+
+    pub(crate) fn free_draw_op(&mut self, user_id: &String, op: DrawOp, timestamp: i64) -> ApiResult<()> {
+        self.check_free_drawer(user_id)?;
+        if self.draw_data.len() >= MAX_FREE_DRAW_UNITS {
+            return Err(ApiError::InvalidPayload)
+        }
+        if self.draw_data.draw_op_by(op, timestamp, user_id) {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidPayload)
+        }
+    }
+
+    /// 只撤销自己的最后一笔
+    pub(crate) fn free_draw_undo(&mut self, user_id: &String) -> ApiResult<()> {
+        self.check_free_drawer(user_id)?;
+        if self.draw_data.undo_by(user_id) {
+            self.draw_revision += 1;
+        }
+        Ok(())
+    }
+
+    /// 只清除自己的笔画
+    pub(crate) fn free_draw_clear(&mut self, user_id: &String) -> ApiResult<()> {
+        self.check_free_drawer(user_id)?;
+        if self.draw_data.clear_by(user_id) {
+            self.draw_revision += 1;
+        }
+        Ok(())
+    }
+
+    pub fn free_draw_snapshot(&self, draw_index: usize) -> FreeDrawSnapshot {
+        FreeDrawSnapshot {
+            draw_list: self.draw_data.slice(draw_index).to_vec(),
+            background: self.background.clone(),
+            revision: self.draw_revision,
+        }
+    }
+}
+
+#[test]
+fn test_free_draw() {
+    let mut room = RoomData::with_players(0..3);
+    room.move_to_observer(&"2".to_string()).unwrap();
+    let id = |id: &str| id.to_string();
+    let stroke = |data: &str| DrawOp::Path { data: data.to_string() };
+    room.start_free_draw();
+    room.draw(&id("0"), "2;#000000;1|".into(), 1).unwrap();
+    room.draw(&id("1"), "2;#ff0000;1|".into(), 2).unwrap();
+    room.draw_op(&id("0"), stroke("4;#000000;1|"), 3).unwrap();
+    assert_eq!(room.draw(&id("2"), "2;#000000;1|".into(), 4), Err(ApiError::NotSeated));
+    room.set_background(&id("1"), &"#eeeeee".to_string()).unwrap();
+
+    // 撤销只影响自己的笔画
+    let revision = room.draw_revision;
+    room.undo_draw(&id("1")).unwrap();
+    assert_eq!(room.draw_revision, revision + 1);
+    let snapshot = room.free_draw_snapshot(0);
+    assert_eq!(snapshot.draw_list.len(), 2);
+    assert!(snapshot.draw_list.iter().all(|unit| unit.author.as_deref() == Some("0")));
+    assert_eq!(snapshot.background, "#eeeeee");
+    room.undo_draw(&id("1")).unwrap();
+    assert_eq!(room.draw_revision, revision + 1);
+    room.clear_draw(&id("0")).unwrap();
+    assert_eq!(room.draw_data.len(), 0);
+
+    assert_eq!(room.end_free_draw(&id("1")), Err(ApiError::NotOwner));
+    // 笔画数达到上限后不能再画
+    for timestamp in 0..MAX_FREE_DRAW_UNITS as i64 {
+        room.draw_op(&id("1"), stroke("2;#000000;1|"), timestamp).unwrap();
+    }
+    assert_eq!(room.draw(&id("0"), "2;#000000;1|".into(), 0), Err(ApiError::InvalidPayload));
+    room.undo_draw(&id("1")).unwrap();
+    room.draw(&id("0"), "2;#000000;1|".into(), 0).unwrap();
+
+    room.end_free_draw(&id("0")).unwrap();
+    assert_eq!(room.stage, GameStage::Ready);
+}
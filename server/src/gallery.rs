@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{draw_data::DrawDataUnit, render::CanvasSource, replay::RoundEvent};
+
+/// 一次猜对的记录
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Guess {
+    pub user_id: String,
+    pub nick_name: String,
+    /// 第几个猜对
+    pub order: usize,
+    pub score: u16,
+    pub timestamp: i64,
+}
+
+/// 一个回合的存档
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundRecord {
+    /// 回合序号，从 0 开始
+    pub round: usize,
+    pub word: String,
+    pub drawer_id: String,
+    pub drawer_name: Option<String>,
+    pub background: String,
+    pub draw_list: Vec<DrawDataUnit>,
+    /// 按猜对顺序排列
+    pub guessers: Vec<Guess>,
+    /// 本回合得分，包括画图的玩家
+    pub scores: HashMap<String, u16>,
+    /// 回合内的事件，用于回放
+    #[serde(skip)]
+    pub events: Vec<RoundEvent>,
+    /// 开始绘制的时间
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+/// 画廊列表中的回合概要，不含笔画数据
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundSummary<'a> {
+    pub round: usize,
+    pub word: &'a str,
+    pub drawer_id: &'a str,
+    pub drawer_name: Option<&'a str>,
+    pub guessers: &'a [Guess],
+    pub scores: &'a HashMap<String, u16>,
+    pub draw_length: usize,
+}
+
+impl RoundRecord {
+    pub fn summary(&self) -> RoundSummary {
+        RoundSummary {
+            round: self.round,
+            word: &self.word,
+            drawer_id: &self.drawer_id,
+            drawer_name: self.drawer_name.as_deref(),
+            guessers: &self.guessers,
+            scores: &self.scores,
+            draw_length: self.draw_list.len(),
+        }
+    }
+
+    /// 回合结束时的画布，缩略图按需在 GameActor 之外渲染
+    pub fn canvas(&self) -> CanvasSource {
+        CanvasSource {
+            draw_data: self.draw_list.clone().into(),
+            background: self.background.clone(),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::room::{GameStage, RoomData, MAX_PEOPLE};
+
+/// 经典模式游戏进行中加入房间的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum MidGameJoin {
+    /// 游戏中不能加入
+    Closed,
+    /// 只能观战
+    Observe,
+    /// 可以坐到空座位上，本局轮到时画图
+    Seat,
+}
+
+impl Default for MidGameJoin {
+    fn default() -> Self {
+        MidGameJoin::Observe
+    }
+}
+
+/// 中途坐下的玩家的初始分
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LateJoinScore {
+    /// 从 0 分开始
+    Zero,
+    /// 座位上其他玩家的最低分
+    Lowest,
+    /// 座位上其他玩家的平均分
+    Average,
+}
+
+impl Default for LateJoinScore {
+    fn default() -> Self {
+        LateJoinScore::Zero
+    }
+}
+
+impl RoomData {
+    /// 经典模式的回合是否在进行中
+    pub fn in_game(&self) -> bool {
+        matches!(self.stage, GameStage::Choose | GameStage::Drawing | GameStage::Result)
+    }
+
+    /// 当前阶段中途加入的方式，`None` 表示和准备阶段一样有空座位就能坐下
+    pub fn mid_game_join(&self) -> Option<MidGameJoin> {
+        match self.stage {
+            // 协作画板没有轮次，坐下就能一起画
+            GameStage::Ready | GameStage::FreeDraw => None,
+            // 传话的链条在开始时已经定好，中途加入最多只能观战
+            GameStage::Telephone => match self.settings.mid_game_join {
+                MidGameJoin::Closed => Some(MidGameJoin::Closed),
+                _ => Some(MidGameJoin::Observe),
+            },
+            _ => Some(self.settings.mid_game_join),
+        }
+    }
+
+    /// 游戏中能否加入，包括观战
+    pub fn accepts_late_join(&self) -> bool {
+        self.stage != GameStage::Ready
+            && self.mid_game_join() != Some(MidGameJoin::Closed)
+            && (self.join_seat().is_some() || self.can_observe())
+    }
+
+    /// 新加入的玩家能坐的座位
+    pub(crate) fn join_seat(&self) -> Option<usize> {
+        match self.mid_game_join() {
+            None => self.free_seat(),
+            Some(_) => self.late_seat(),
+        }
+    }
+
+    /// 中途加入时能坐的座位：当前画图玩家之后的空座位，按 `next_player` 的顺序本局还能轮到
+    pub fn late_seat(&self) -> Option<usize> {
+        if self.mid_game_join() != Some(MidGameJoin::Seat) {
+            return None
+        }
+        let reserved = self.reserved_seat();
+        (0..MAX_PEOPLE)
+            .filter(|pos| self.turn_ahead(*pos))
+            .find(|pos| self.seat[*pos].is_none() && !self.locked_seats[*pos] && reserved != Some(*pos))
+    }
+
+    /// 座位在当前画图玩家之后，本局还能轮到
+    pub(crate) fn turn_ahead(&self, pos: usize) -> bool {
+        self.cur_seat.map_or(true, |cur| pos > cur)
+    }
+
+    /// 中途坐下的玩家按设置获得初始分，已有成绩的不变
+    pub fn apply_late_score(&mut self, user_id: &String) {
+        if self.settlement.contains_key(user_id) {
+            return
+        }
+        let others = self.seat.iter()
+            .flatten()
+            .filter(|id| *id != user_id)
+            .map(|id| self.settlement.get(id).copied().unwrap_or(0) as u32)
+            .collect::<Vec<_>>();
+        let score = match self.settings.late_join_score {
+            LateJoinScore::Zero => 0,
+            LateJoinScore::Lowest => others.iter().min().copied().unwrap_or(0),
+            LateJoinScore::Average if others.is_empty() => 0,
+            LateJoinScore::Average => others.iter().sum::<u32>() / others.len() as u32,
+        };
+        if score > 0 {
+            self.settlement.insert(user_id.clone(), score as u16);
+        }
+    }
+}
+
+#[test]
+fn test_late_join() {
+    use crate::{error::ApiError, player::Player, room::SettingsPatch};
+
+    let id = |id: &str| id.to_string();
+    let mut room = RoomData::with_players(&["1", "2", "3"]);
+    room.seat.swap(2, 4);
+    room.stage = GameStage::Drawing;
+    room.cur_seat = Some(1);
+    room.cur_id = Some(id("2"));
+    room.settlement.insert(id("1"), 6);
+    room.settlement.insert(id("2"), 10);
+
+    // 默认只能观战
+    assert!(room.accepts_late_join());
+    room.join_player(&Player::test("4"));
+    assert!(room.observer.contains("4"));
+    assert_eq!(room.sit_on(&id("4"), 2), Err(ApiError::WrongStage));
+
+    room.stage = GameStage::Ready;
+    room.update_settings(&id("1"), SettingsPatch {
+        mid_game_join: Some(MidGameJoin::Seat),
+        late_join_score: Some(LateJoinScore::Average),
+        ..Default::default()
+    }).unwrap();
+    room.stage = GameStage::Drawing;
+    // 跳过当前画图玩家之前的座位
+    assert_eq!(room.late_seat(), Some(2));
+    room.join_player(&Player::test("5"));
+    assert_eq!(room.seat[2].as_deref(), Some("5"));
+    assert_eq!(room.settlement.get("5"), Some(&5));
+
+    // 观战玩家只能坐到本局还能轮到的座位
+    room.cur_seat = Some(4);
+    room.cur_id = Some(id("3"));
+    assert_eq!(room.sit_on(&id("4"), 3), Err(ApiError::WrongStage));
+    room.sit_on(&id("4"), 5).unwrap();
+    assert_eq!(room.seat[5].as_deref(), Some("4"));
+
+    room.stage = GameStage::Ready;
+    room.update_settings(&id("1"), SettingsPatch { mid_game_join: Some(MidGameJoin::Closed), ..Default::default() }).unwrap();
+    room.stage = GameStage::Drawing;
+    assert!(!room.accepts_late_join());
+    assert_eq!(room.check_capacity(&id("6")), Err(ApiError::WrongStage));
+
+    // 传话的链条开始时已经定好，允许中途坐下也只能观战
+    room.stage = GameStage::Telephone;
+    assert!(!room.in_game());
+    assert_eq!(room.check_capacity(&id("6")), Err(ApiError::WrongStage));
+    room.stage = GameStage::Ready;
+    room.update_settings(&id("1"), SettingsPatch { mid_game_join: Some(MidGameJoin::Seat), ..Default::default() }).unwrap();
+    room.stage = GameStage::Telephone;
+    assert!(room.accepts_late_join());
+    assert_eq!(room.late_seat(), None);
+    room.join_player(&Player::test("6"));
+    assert!(room.observer.contains("6"));
+    assert_eq!(room.sit_on(&id("6"), 3), Err(ApiError::WrongStage));
+
+    // 协作画板没有轮次，当前画图玩家之前的空座位也能坐
+    room.stage = GameStage::FreeDraw;
+    assert!(room.accepts_late_join());
+    room.sit_on(&id("6"), 3).unwrap();
+    assert!(!room.settlement.contains_key("6"));
+}
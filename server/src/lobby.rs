@@ -0,0 +1,139 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{ApiError, ApiResult}, message::ListRoomsMsg, room::{RoomData, RoomMeta}};
+
+/// 默认每页房间数
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+/// 每页最多房间数
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// 房间列表排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum RoomSort {
+    /// 按房间id，结果最稳定
+    Id,
+    /// 坐下人数多的在前
+    Players,
+    /// 观战人数多的在前
+    Observers,
+}
+
+impl Default for RoomSort {
+    fn default() -> Self {
+        RoomSort::Id
+    }
+}
+
+/// 排序键，游标就是上一页最后一个房间的排序键
+type SortKey = (i64, String);
+
+impl RoomSort {
+    fn key(&self, room: &RoomData) -> SortKey {
+        let primary = match self {
+            RoomSort::Id => 0,
+            RoomSort::Players => -(room.seat.iter().flatten().count() as i64),
+            RoomSort::Observers => -(room.observer.len() as i64),
+        };
+        (primary, room.id.clone())
+    }
+}
+
+fn format_cursor(key: &SortKey) -> String {
+    format!("{}:{}", key.0, key.1)
+}
+
+fn parse_cursor(cursor: &str) -> ApiResult<SortKey> {
+    let (primary, id) = cursor.split_once(':').ok_or(ApiError::InvalidPayload)?;
+    let primary = primary.parse().map_err(|_| ApiError::InvalidPayload)?;
+    Ok((primary, id.to_string()))
+}
+
+/// 房间列表的一页
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomPage {
+    pub rooms: Vec<RoomMeta>,
+    /// 下一页的游标，没有更多时为空
+    pub next_cursor: Option<String>,
+    /// 符合条件的房间总数
+    pub total: usize,
+    /// 符合条件的房间内的总人数，含观战
+    pub online: usize,
+}
+
+/// 过滤、排序公开房间，并按游标分页
+pub fn list_rooms<'a, I>(rooms: I, query: &ListRoomsMsg) -> ApiResult<RoomPage>
+where
+    I: IntoIterator<Item = &'a RoomData>,
+{
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let after = query.cursor.as_deref().map(parse_cursor).transpose()?;
+    let mut rooms = rooms.into_iter()
+        .filter(|room| !room.private)
+        .filter(|room| !query.joinable || room.can_join())
+        .filter(|room| query.language.as_ref().map_or(true, |language| &room.settings.language == language))
+        .filter(|room| query.word_pack.as_ref().map_or(true, |word_pack| &room.settings.word_pack == word_pack))
+        .map(|room| (query.sort.key(room), room))
+        .collect::<Vec<_>>();
+    let total = rooms.len();
+    let online = rooms.iter()
+        .map(|(_, room)| room.players.len())
+        .sum();
+    rooms.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut page = rooms.into_iter()
+        .filter(|(key, _)| after.as_ref().map_or(true, |after| key > after))
+        .take(limit + 1)
+        .collect::<Vec<_>>();
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|(key, _)| format_cursor(key))
+    } else {
+        None
+    };
+    Ok(RoomPage {
+        rooms: page.into_iter().map(|(_, room)| RoomMeta::from_room_data(room)).collect(),
+        next_cursor,
+        total,
+        online,
+    })
+}
+
+#[test]
+fn test_list_rooms() {
+    use crate::{matchmaker::MatchPreference, player::Player};
+
+    let mut rooms = (0..5).map(|i| RoomData::with_players((0..i).map(|j| i * 10 + j))).collect::<Vec<_>>();
+    rooms.push(RoomData::new_private(None, "ABCDEF".to_string()));
+    let mut english = MatchPreference { language: Some("en".to_string()), word_pack: None }.new_room();
+    english.join_player(&Player::test("100"));
+    rooms.push(english);
+
+    let query = ListRoomsMsg {
+        sort: RoomSort::Players,
+        limit: Some(2),
+        ..Default::default()
+    };
+    let first = list_rooms(&rooms, &query).unwrap();
+    assert_eq!(first.total, 6);
+    assert_eq!(first.online, 11);
+    assert_eq!(first.rooms.iter().map(|room| room.seated).collect::<Vec<_>>(), vec![4, 3]);
+
+    let mut seen = first.rooms.len();
+    let mut cursor = first.next_cursor;
+    while let Some(next) = cursor {
+        let page = list_rooms(&rooms, &ListRoomsMsg { cursor: Some(next), ..query.clone() }).unwrap();
+        seen += page.rooms.len();
+        cursor = page.next_cursor;
+    }
+    assert_eq!(seen, 6);
+
+    let english = ListRoomsMsg { language: Some("en".to_string()), ..Default::default() };
+    let page = list_rooms(&rooms, &english).unwrap();
+    assert_eq!(page.rooms.len(), 1);
+    assert_eq!(page.rooms[0].language, "en");
+
+    let bad = ListRoomsMsg { cursor: Some("abc".to_string()), ..Default::default() };
+    assert_eq!(list_rooms(&rooms, &bad).unwrap_err(), ApiError::InvalidPayload);
+}
@@ -4,8 +4,26 @@ mod room;
 mod player;
 mod message;
 mod wrap;
+mod error;
 mod word;
 mod draw_data;
+mod render;
+mod gallery;
+mod replay;
+mod animation;
+mod openapi;
+mod snapshot;
+mod vote;
+mod matchmaker;
+mod queue;
+mod lobby;
+mod spectator;
+mod seat_queue;
+mod late_join;
+mod team;
+mod telephone;
+mod free_draw;
+mod custom_words;
 mod log;
 mod graph;
 
@@ -0,0 +1,118 @@
+use std::cmp::Reverse;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::room::{is_valid_tag, RoomData};
+
+/// 匹配偏好，`None` 表示不限
+#[derive(Debug, Default, Clone)]
+pub struct MatchPreference {
+    pub language: Option<String>,
+    pub word_pack: Option<String>,
+}
+
+/// 匹配结果
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchResult {
+    pub room_id: String,
+    /// 没有合适的房间，为加入的玩家新建了一个
+    pub created: bool,
+    /// 已经把玩家加入房间
+    pub joined: bool,
+}
+
+impl MatchPreference {
+    pub fn is_valid(&self) -> bool {
+        self.language.as_deref().map_or(true, is_valid_tag)
+            && self.word_pack.as_deref().map_or(true, is_valid_tag)
+    }
+
+    fn accepts(&self, room: &RoomData) -> bool {
+        self.language.as_ref().map_or(true, |language| &room.settings.language == language)
+            && self.word_pack.as_ref().map_or(true, |word_pack| &room.settings.word_pack == word_pack)
+    }
+
+    /// 按偏好新建一个公开房间
+    pub fn new_room(&self) -> RoomData {
+        let mut room = RoomData::new();
+        if let Some(language) = &self.language {
+            room.settings.language = language.clone();
+        }
+        if let Some(word_pack) = &self.word_pack {
+            room.settings.word_pack = word_pack.clone();
+        }
+        room
+    }
+}
+
+impl RoomData {
+    /// 座位上已准备的人数
+    fn ready_count(&self) -> usize {
+        self.seat.iter()
+            .flatten()
+            .filter(|id| self.ready_state.get(*id).is_some())
+            .count()
+    }
+
+    fn seated_count(&self) -> usize {
+        self.seat.iter().flatten().count()
+    }
+}
+
+/// 在可加入的公开房间中挑选最接近开局的：准备人数最多，其次坐下人数最多；都开局了则进入允许中途加入的房间
+pub fn pick_room<'a, I>(rooms: I, preference: &MatchPreference, user_id: Option<&String>) -> Option<&'a RoomData>
+where
+    I: IntoIterator<Item = &'a RoomData>,
+{
+    rooms.into_iter()
+        .filter(|room| !room.private && (room.can_join() || room.accepts_late_join()) && preference.accepts(room))
+        .filter(|room| user_id.map_or(true, |id| !room.banned.contains(id)))
+        // 优先能坐下的房间，同分时按 id 取，保证结果稳定
+        .max_by_key(|room| (room.can_join(), room.ready_count(), room.seated_count(), Reverse(&room.id)))
+}
+
+#[test]
+fn test_pick_room() {
+    use crate::player::Player;
+
+    let mut quiet = RoomData::new();
+    quiet.join_player(&Player::test("1"));
+    quiet.join_player(&Player::test("2"));
+    let mut almost = RoomData::new();
+    almost.join_player(&Player::test("3"));
+    almost.ready(&"3".to_string());
+    let mut private = RoomData::new_private(None, "ABCDEF".to_string());
+    private.join_player(&Player::test("4"));
+    private.ready(&"4".to_string());
+    let mut english = MatchPreference { language: Some("en".to_string()), word_pack: None }.new_room();
+    english.join_player(&Player::test("5"));
+    english.ready(&"5".to_string());
+    english.join_player(&Player::test("6"));
+
+    let rooms = vec![quiet, almost, private, english];
+    let any = MatchPreference::default();
+    let picked = pick_room(&rooms, &any, None).unwrap();
+    assert_eq!(picked.settings.language, "en");
+
+    let chinese = MatchPreference { language: Some("zh".to_string()), word_pack: None };
+    let picked = pick_room(&rooms, &chinese, None).unwrap();
+    assert_eq!(picked.id, rooms[1].id);
+
+    let mut rooms = rooms;
+    rooms[1].banned.insert("7".to_string());
+    let picked = pick_room(&rooms, &chinese, Some(&"7".to_string())).unwrap();
+    assert_eq!(picked.id, rooms[0].id);
+
+    let german = MatchPreference { language: Some("de".to_string()), word_pack: None };
+    assert!(pick_room(&rooms, &german, None).is_none());
+
+    // 游戏中的房间默认可以进去观战
+    let mut playing = german.new_room();
+    playing.stage = crate::room::GameStage::Drawing;
+    rooms.push(playing);
+    assert_eq!(pick_room(&rooms, &german, None).unwrap().id, rooms[4].id);
+
+    assert!(!MatchPreference { language: Some("".to_string()), word_pack: None }.is_valid());
+}
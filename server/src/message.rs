@@ -1,39 +1,59 @@
 use std::time::Duration;
 
 use actix::Message;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::player::Player;
+use crate::{player::Player, team::Team, late_join::{LateJoinScore, MidGameJoin}, queue::QueueStatus, lobby::{RoomPage, RoomSort}, matchmaker::MatchResult, vote::VoteType, room::{GameMode, RoomCreated}, draw_data::{DrawOp, CANVAS_SIZE}, render::{CanvasFormat, CanvasSource}, replay::Replay, snapshot::{InitSnapshot, PollSnapshot, RoomView}, error::ApiResult};
 
-#[derive(Debug, Message)]
-#[rtype(result = "String")]
-pub struct CreateRoomMsg {}
+/// 创建房间，设置了密码或 `private` 时为私密房间
+#[derive(Debug, Default, Message, Deserialize, JsonSchema)]
+#[rtype(result = "RoomCreated")]
+pub struct CreateRoomMsg {
+    #[serde(default)]
+    pub private: bool,
+    pub password: Option<String>,
+}
 
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct JoinRoomMsg {
     pub player: Player,
-    #[serde(rename = "roomId")]
+    /// 凭邀请码加入时可以不传
+    #[serde(rename = "roomId", default)]
     pub room_id: String,
     pub token: String,
+    /// 私密房间的密码
+    pub password: Option<String>,
+    /// 私密房间的邀请码
+    #[serde(rename = "inviteCode")]
+    pub invite_code: Option<String>,
 }
 
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "Option<Value>")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<InitSnapshot>")]
 pub struct InitMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
 }
 
-#[derive(Debug, Message)]
-#[rtype(result = "Option<Value>")]
+/// 获取房间信息，按身份返回不同的数据
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<RoomView>")]
+#[serde(rename_all = "camelCase")]
 pub struct GetRoomInfoMsg {
-    pub id: String
+    #[serde(skip)]
+    pub id: String,
+    /// 不传则按房间外的访客处理
+    pub user_id: Option<String>,
+    /// 管理员口令，取自 `Authorization` 请求头
+    #[serde(skip)]
+    pub admin_token: Option<String>,
 }
 
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "()")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct RoomReadyMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -41,16 +61,16 @@ pub struct RoomReadyMsg {
 }
 
 /// 从座位到观战列表
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct RoomGetUpMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
 }
 
 /// 从观战列表到座位
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct RoomSitOnMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -58,8 +78,8 @@ pub struct RoomSitOnMsg {
 }
 
 /// 轮训数据
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "Option<Value>")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<PollSnapshot>")]
 pub struct PollingMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -72,8 +92,8 @@ pub struct PollingMsg {
 }
 
 /// 轮训数据
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct ChatMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -81,8 +101,8 @@ pub struct ChatMsg {
 }
 
 /// 玩家离开房间
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "()")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct LeaveRoomMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -93,6 +113,23 @@ pub struct LeaveRoomMsg {
 #[rtype(result = "Option<Value>")]
 pub struct GetAllRoomMsg {}
 
+/// 分页查询房间列表
+#[derive(Debug, Default, Clone, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<RoomPage>")]
+#[serde(rename_all = "camelCase")]
+pub struct ListRoomsMsg {
+    /// 只返回还能加入的房间
+    #[serde(default)]
+    pub joinable: bool,
+    pub language: Option<String>,
+    pub word_pack: Option<String>,
+    #[serde(default)]
+    pub sort: RoomSort,
+    /// 上一页返回的 `nextCursor`
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
 #[derive(Debug, Message)]
 #[rtype(result = "()")]
 pub struct GameStartMsg {
@@ -113,8 +150,8 @@ pub struct GameStartCancelMsg {
 }
 
 /// 选词
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct ChooseWordMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
@@ -122,8 +159,8 @@ pub struct ChooseWordMsg {
 }
 
 /// 画图
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 #[serde(rename_all = "camelCase")]
 pub struct DrawMsg {
     pub user_id: String,
@@ -131,17 +168,27 @@ pub struct DrawMsg {
     pub timestamp: i64,
 }
 
+/// 图形绘制（直线、矩形、椭圆、填充、橡皮擦）
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct DrawOpMsg {
+    pub user_id: String,
+    pub op: DrawOp,
+    pub timestamp: i64,
+}
+
 /// 清空画布
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct DrawClearMsg {
     #[serde(rename = "userId")]
     pub user_id: String
 }
 
 /// 笔画撤销
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "bool")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct DrawUndoMsg {
     #[serde(rename = "userId")]
     pub user_id: String
@@ -178,15 +225,273 @@ pub struct NextPlayerDrawMsg {
 
 
 /// 修改画布颜色
-#[derive(Debug, Message, Deserialize)]
-#[rtype(result = "()")]
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
 pub struct DrawChangeBackgoundMsg {
     #[serde(rename = "userId")]
     pub user_id: String,
     pub color: String,
 }
 
-/// 查找能加入的房间，没有则返回失败
+/// 匹配一个能加入的房间；带上玩家信息时直接加入，没有合适的房间则新建
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<MatchResult>")]
+#[serde(rename_all = "camelCase")]
+pub struct FindJoinableRoomMsg {
+    pub language: Option<String>,
+    pub word_pack: Option<String>,
+    pub user_id: Option<String>,
+    pub nick_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// 导出房间当前画布，GameActor 只负责复制画布
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<CanvasSource>")]
+pub struct RenderCanvasMsg {
+    #[serde(skip)]
+    pub room_id: String,
+    /// 导出格式，由路由决定
+    #[serde(skip)]
+    pub format: CanvasFormat,
+    /// 图片边长，默认与画布一致
+    #[serde(default = "default_render_size")]
+    pub size: u32,
+}
+
+fn default_render_size() -> u32 {
+    CANVAS_SIZE as u32
+}
+
+/// 获取某一回合结束时的画布，用于渲染缩略图
 #[derive(Debug, Message)]
-#[rtype(result = "Option<Value>")]
-pub struct FindJoinableRoomMsg {}
\ No newline at end of file
+#[rtype(result = "ApiResult<CanvasSource>")]
+pub struct RoundThumbnailMsg {
+    pub room_id: String,
+    pub round: usize,
+}
+
+/// 获取本局画廊（各回合概要）
+#[derive(Debug, Message)]
+#[rtype(result = "ApiResult<Value>")]
+pub struct GetGalleryMsg {
+    pub room_id: String,
+}
+
+/// 获取画廊中某一回合的详细数据
+#[derive(Debug, Message)]
+#[rtype(result = "ApiResult<Value>")]
+pub struct GetGalleryRoundMsg {
+    pub room_id: String,
+    pub round: usize,
+}
+
+/// 获取某一回合的回放
+#[derive(Debug, Message)]
+#[rtype(result = "ApiResult<Replay>")]
+pub struct GetReplayMsg {
+    pub room_id: String,
+    pub round: usize,
+}
+
+/// 房主把玩家踢出房间
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct KickPlayerMsg {
+    pub user_id: String,
+    pub target_id: String,
+}
+
+/// 房主封禁玩家，房间存在期间不能再加入
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct BanPlayerMsg {
+    pub user_id: String,
+    pub target_id: String,
+}
+
+/// 转让房主
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOwnerMsg {
+    pub user_id: String,
+    pub target_id: String,
+}
+
+/// 锁定/解锁座位
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct LockSeatMsg {
+    pub user_id: String,
+    pub pos: u8,
+    pub locked: bool,
+}
+
+/// 修改房间设置，不传的字段保持不变
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSettingsMsg {
+    pub user_id: String,
+    pub name: Option<String>,
+    pub choose_time: Option<u8>,
+    pub draw_time: Option<u8>,
+    pub language: Option<String>,
+    pub word_pack: Option<String>,
+    pub max_observers: Option<u8>,
+    pub observer_chat: Option<bool>,
+    pub guess_along: Option<bool>,
+    pub mid_game_join: Option<MidGameJoin>,
+    pub late_join_score: Option<LateJoinScore>,
+    pub mode: Option<GameMode>,
+    pub team_mode: Option<bool>,
+    pub steal_delay: Option<u8>,
+    pub custom_word_ratio: Option<u8>,
+}
+
+/// 房主强制开始
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct ForceStartMsg {
+    pub user_id: String,
+}
+
+/// 发起投票：踢人需要 `targetId`，跳过回合针对当前画图的玩家
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct StartVoteMsg {
+    pub user_id: String,
+    pub kind: VoteType,
+    pub target_id: Option<String>,
+}
+
+/// 对正在进行的投票表态
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct CastVoteMsg {
+    pub user_id: String,
+    pub agree: bool,
+}
+
+/// 投票到期
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct VoteExpireMsg {
+    pub room_id: String,
+    pub seq: u32,
+}
+
+/// 加入快速匹配队列，之后需要持续查询排队状态，否则会被移出队列
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<QueueStatus>")]
+#[serde(rename_all = "camelCase")]
+pub struct JoinQueueMsg {
+    pub user_id: String,
+    pub nick_name: String,
+    #[serde(default)]
+    pub avatar_url: String,
+    pub language: Option<String>,
+}
+
+/// 离开快速匹配队列
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct LeaveQueueMsg {
+    pub user_id: String,
+}
+
+/// 查询排队状态，分配房间后返回房间id
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<QueueStatus>")]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStatusMsg {
+    pub user_id: String,
+}
+
+/// 时间窗口结束，把排队的玩家分配到房间
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct FlushQueueMsg {}
+
+/// 观战玩家排队等座位
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct JoinSeatQueueMsg {
+    pub user_id: String,
+}
+
+/// 退出座位排队
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct LeaveSeatQueueMsg {
+    pub user_id: String,
+}
+
+/// 留座到期
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct SeatOfferExpireMsg {
+    pub room_id: String,
+    pub seq: u32,
+}
+
+/// 团队模式下选择队伍
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct ChooseTeamMsg {
+    pub user_id: String,
+    pub team: Team,
+}
+
+/// 传话模式提交本步：写题目与描述需要 `text`，画图时表示画完了
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct TelephoneSubmitMsg {
+    pub user_id: String,
+    pub text: Option<String>,
+}
+
+/// 房主结束协作画板
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<()>")]
+#[serde(rename_all = "camelCase")]
+pub struct EndFreeDrawMsg {
+    pub user_id: String,
+}
+
+/// 房主上传自定义词库，每行一个或以逗号分隔，传空表示不再使用
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "ApiResult<usize>")]
+#[serde(rename_all = "camelCase")]
+pub struct SetCustomWordsMsg {
+    pub user_id: String,
+    pub words: String,
+}
+
+/// 获取候选词，带上 `userId` 时按所在房间的词库与自定义词库出词
+#[derive(Debug, Message, Deserialize, JsonSchema)]
+#[rtype(result = "Vec<String>")]
+#[serde(rename_all = "camelCase")]
+pub struct RandomWordsMsg {
+    pub user_id: Option<String>,
+}
+
+/// 传话模式的步骤到时
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct TelephoneStepMsg {
+    pub room_id: String,
+    pub seq: u32,
+}
@@ -0,0 +1,325 @@
+use once_cell::sync::Lazy;
+use schemars::{gen::{SchemaGenerator, SchemaSettings}, schema::{RootSchema, Schema}, JsonSchema};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    animation::AnimationOptions,
+    error::ApiError,
+    gallery::{RoundRecord, RoundSummary},
+    matchmaker::MatchResult,
+    lobby::RoomPage,
+    queue::QueueStatus,
+    message::*,
+    replay::Replay,
+    room::{RoomCreated, RoomMeta},
+    snapshot::{InitSnapshot, PollSnapshot, RoomView},
+    word::ReloadWordsResult,
+};
+
+/// 生成好的文档，进程内只生成一次
+pub static SPEC: Lazy<Value> = Lazy::new(spec);
+
+const V1: &[&str] = &["v1"];
+const V2: &[&str] = &["v2"];
+const BOTH: &[&str] = &["v1", "v2"];
+
+/// 请求参数的来源
+enum Input {
+    None,
+    Query(fn(&mut SchemaGenerator) -> RootSchema),
+    Json(fn(&mut SchemaGenerator) -> Schema),
+    /// 原样读取的请求体（回放文件）
+    Text,
+}
+
+/// 成功时的返回
+enum Output {
+    Json(fn(&mut SchemaGenerator) -> Schema),
+    File(&'static str),
+}
+
+/// 一个接口，`path` 为 scope 内的相对路径，与 app.rs 中的路由宏一致
+struct Route {
+    versions: &'static [&'static str],
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    input: Input,
+    output: Output,
+}
+
+fn query<T: JsonSchema>(gen: &mut SchemaGenerator) -> RootSchema {
+    gen.root_schema_for::<T>()
+}
+
+fn json<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+/// 修改类接口成功时的返回
+#[derive(Serialize, JsonSchema)]
+struct SuccBody {
+    succ: bool,
+}
+
+fn routes() -> Vec<Route> {
+    use Input::*;
+    let any = || Output::Json(json::<Value>);
+    vec![
+        Route { versions: V1, method: "get", path: "/roomCreate", summary: "创建房间", input: Query(query::<CreateRoomMsg>), output: Output::Json(json::<RoomCreated>) },
+        Route { versions: V2, method: "post", path: "/roomCreate", summary: "创建房间", input: Json(json::<CreateRoomMsg>), output: Output::Json(json::<RoomCreated>) },
+        Route { versions: BOTH, method: "post", path: "/roomJoin", summary: "加入房间", input: Json(json::<JoinRoomMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: BOTH, method: "get", path: "/all", summary: "获取所有房间", input: None, output: Output::Json(json::<Vec<RoomMeta>>) },
+        Route { versions: BOTH, method: "get", path: "/rooms", summary: "房间列表", input: Query(query::<ListRoomsMsg>), output: Output::Json(json::<RoomPage>) },
+        Route { versions: BOTH, method: "get", path: "/init", summary: "初始化", input: Query(query::<InitMsg>), output: Output::Json(json::<InitSnapshot>) },
+        Route { versions: V1, method: "get", path: "/leave", summary: "离开房间", input: Query(query::<LeaveRoomMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/leave", summary: "离开房间", input: Json(json::<LeaveRoomMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: BOTH, method: "get", path: "/poll", summary: "客户端轮训", input: Query(query::<PollingMsg>), output: Output::Json(json::<PollSnapshot>) },
+        Route { versions: BOTH, method: "get", path: "/info/{id}", summary: "获取房间信息，管理员口令放在 Authorization: Bearer 请求头", input: Query(query::<GetRoomInfoMsg>), output: Output::Json(json::<RoomView>) },
+        Route { versions: V1, method: "get", path: "/ready", summary: "房间玩家准备", input: Query(query::<RoomReadyMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/ready", summary: "房间玩家准备", input: Json(json::<RoomReadyMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/getUp", summary: "站起围观", input: Query(query::<RoomGetUpMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/getUp", summary: "站起围观", input: Json(json::<RoomGetUpMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/sitOn", summary: "坐下", input: Query(query::<RoomSitOnMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/sitOn", summary: "坐下", input: Json(json::<RoomSitOnMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/chat", summary: "聊天", input: Query(query::<ChatMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/chat", summary: "聊天", input: Json(json::<ChatMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: BOTH, method: "get", path: "/random", summary: "获取随机词库", input: Query(query::<RandomWordsMsg>), output: Output::Json(json::<Vec<String>>) },
+        Route { versions: V1, method: "get", path: "/choose", summary: "选词", input: Query(query::<ChooseWordMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/choose", summary: "选词", input: Json(json::<ChooseWordMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/draw", summary: "画图", input: Query(query::<DrawMsg>), output: Output::Json(json::<bool>) },
+        Route { versions: V2, method: "post", path: "/draw", summary: "画图", input: Json(json::<DrawMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: BOTH, method: "post", path: "/drawOp", summary: "图形绘制", input: Json(json::<DrawOpMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/undo", summary: "撤销", input: Query(query::<DrawUndoMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/undo", summary: "撤销", input: Json(json::<DrawUndoMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/clear", summary: "清空", input: Query(query::<DrawClearMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/clear", summary: "清空", input: Json(json::<DrawClearMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/setColor", summary: "设置颜色", input: Query(query::<DrawChangeBackgoundMsg>), output: Output::Json(json::<bool>) },
+        Route { versions: V2, method: "post", path: "/setColor", summary: "设置颜色", input: Json(json::<DrawChangeBackgoundMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/kick", summary: "踢人", input: Query(query::<KickPlayerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/kick", summary: "踢人", input: Json(json::<KickPlayerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/ban", summary: "封禁玩家", input: Query(query::<BanPlayerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/ban", summary: "封禁玩家", input: Json(json::<BanPlayerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/transferOwner", summary: "转让房主", input: Query(query::<TransferOwnerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/transferOwner", summary: "转让房主", input: Json(json::<TransferOwnerMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/lockSeat", summary: "锁定座位", input: Query(query::<LockSeatMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/lockSeat", summary: "锁定座位", input: Json(json::<LockSeatMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/settings", summary: "修改房间设置", input: Query(query::<UpdateSettingsMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/settings", summary: "修改房间设置", input: Json(json::<UpdateSettingsMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/forceStart", summary: "强制开始", input: Query(query::<ForceStartMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/forceStart", summary: "强制开始", input: Json(json::<ForceStartMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/vote", summary: "发起投票", input: Query(query::<StartVoteMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/vote", summary: "发起投票", input: Json(json::<StartVoteMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/voteCast", summary: "投票", input: Query(query::<CastVoteMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/voteCast", summary: "投票", input: Json(json::<CastVoteMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/queue", summary: "快速匹配排队", input: Query(query::<JoinQueueMsg>), output: Output::Json(json::<QueueStatus>) },
+        Route { versions: V2, method: "post", path: "/queue", summary: "快速匹配排队", input: Json(json::<JoinQueueMsg>), output: Output::Json(json::<QueueStatus>) },
+        Route { versions: BOTH, method: "get", path: "/queue/status", summary: "排队状态", input: Query(query::<QueueStatusMsg>), output: Output::Json(json::<QueueStatus>) },
+        Route { versions: V1, method: "get", path: "/queue/leave", summary: "取消排队", input: Query(query::<LeaveQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/queue/leave", summary: "取消排队", input: Json(json::<LeaveQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/seatQueue", summary: "排队等座位", input: Query(query::<JoinSeatQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/seatQueue", summary: "排队等座位", input: Json(json::<JoinSeatQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/seatQueue/leave", summary: "退出座位排队", input: Query(query::<LeaveSeatQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/seatQueue/leave", summary: "退出座位排队", input: Json(json::<LeaveSeatQueueMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/team", summary: "选择队伍", input: Query(query::<ChooseTeamMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/team", summary: "选择队伍", input: Json(json::<ChooseTeamMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/telephone/submit", summary: "传话模式提交", input: Query(query::<TelephoneSubmitMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/telephone/submit", summary: "传话模式提交", input: Json(json::<TelephoneSubmitMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V1, method: "get", path: "/freeDraw/end", summary: "结束协作画板", input: Query(query::<EndFreeDrawMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/freeDraw/end", summary: "结束协作画板", input: Json(json::<EndFreeDrawMsg>), output: Output::Json(json::<SuccBody>) },
+        Route { versions: V2, method: "post", path: "/customWords", summary: "上传自定义词库", input: Json(json::<SetCustomWordsMsg>), output: Output::Json(json::<usize>) },
+        Route { versions: V2, method: "post", path: "/admin/reloadWords", summary: "重新加载词库，管理员口令放在 Authorization: Bearer 请求头", input: None, output: Output::Json(json::<ReloadWordsResult>) },
+        Route { versions: BOTH, method: "get", path: "/findJoinableRoom", summary: "随机进房", input: Query(query::<FindJoinableRoomMsg>), output: Output::Json(json::<MatchResult>) },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/canvas.png", summary: "画布导出 png", input: Query(query::<RenderCanvasMsg>), output: Output::File("image/png") },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/canvas.svg", summary: "画布导出 svg", input: Query(query::<RenderCanvasMsg>), output: Output::File("image/svg+xml") },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/thumbnail/{round}", summary: "回合缩略图", input: None, output: Output::File("image/png") },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery", summary: "本局画廊", input: None, output: Output::Json(json::<Vec<RoundSummary<'static>>>) },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery/{round}", summary: "画廊回合详情", input: None, output: Output::Json(json::<RoundRecord>) },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery/{round}/replay", summary: "回合回放", input: None, output: Output::Json(json::<Replay>) },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery/{round}/replay.json", summary: "导出回放文件", input: None, output: Output::Json(json::<Replay>) },
+        Route { versions: BOTH, method: "post", path: "/replay/import", summary: "导入回放文件", input: Text, output: Output::Json(json::<Replay>) },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery/{round}/animation.gif", summary: "绘制过程动画 gif", input: Query(query::<AnimationOptions>), output: Output::File("image/gif") },
+        Route { versions: BOTH, method: "get", path: "/room/{id}/gallery/{round}/animation.png", summary: "绘制过程动画 apng", input: Query(query::<AnimationOptions>), output: Output::File("image/apng") },
+        Route { versions: BOTH, method: "get", path: "/openapi.json", summary: "接口文档", input: None, output: any() },
+    ]
+}
+
+/// 路径中的 `{xxx}` 参数
+fn path_parameters(path: &str) -> Vec<Value> {
+    path.split('/')
+        .filter_map(|seg| seg.strip_prefix('{').and_then(|seg| seg.strip_suffix('}')))
+        .map(|name| {
+            let ty = if name == "round" { "integer" } else { "string" };
+            json!({ "name": name, "in": "path", "required": true, "schema": { "type": ty } })
+        })
+        .collect()
+}
+
+/// 把查询结构体的每个字段展开为 query 参数
+fn query_parameters(root: RootSchema) -> Vec<Value> {
+    let object = match root.schema.object {
+        Some(object) => object,
+        None => return vec![],
+    };
+    object.properties.iter()
+        .map(|(name, schema)| json!({
+            "name": name,
+            "in": "query",
+            "required": object.required.contains(name),
+            "schema": schema,
+        }))
+        .collect()
+}
+
+fn operation(route: &Route, gen: &mut SchemaGenerator) -> Value {
+    let mut parameters = path_parameters(route.path);
+    let mut op = Map::new();
+    op.insert("summary".into(), json!(route.summary));
+    match &route.input {
+        Input::None => (),
+        Input::Query(f) => parameters.extend(query_parameters(f(gen))),
+        Input::Json(f) => {
+            op.insert("requestBody".into(), json!({
+                "required": true,
+                "content": { "application/json": { "schema": f(gen) } },
+            }));
+        },
+        Input::Text => {
+            op.insert("requestBody".into(), json!({
+                "required": true,
+                "content": { "application/json": { "schema": json::<Replay>(gen) } },
+            }));
+        },
+    }
+    if !parameters.is_empty() {
+        op.insert("parameters".into(), json!(parameters));
+    }
+    let ok = match &route.output {
+        Output::Json(f) => json!({
+            "description": "成功",
+            "content": { "application/json": { "schema": f(gen) } },
+        }),
+        Output::File(content_type) => json!({
+            "description": "成功",
+            "content": { *content_type: { "schema": { "type": "string", "format": "binary" } } },
+        }),
+    };
+    op.insert("responses".into(), json!({
+        "200": ok,
+        "default": {
+            "description": "错误",
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/ApiError" } } },
+        },
+    }));
+    Value::Object(op)
+}
+
+/// 根据 message.rs / room.rs / player.rs 中的类型生成 OpenAPI 3 文档
+pub fn spec() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+    for route in routes() {
+        let op = operation(&route, &mut gen);
+        for version in route.versions {
+            let item = paths.entry(format!("/{}{}", version, route.path))
+                .or_insert_with(|| json!({}));
+            item[route.method] = op.clone();
+        }
+    }
+    let mut schemas = serde_json::to_value(gen.definitions()).unwrap_or_else(|_| json!({}));
+    schemas["ApiError"] = json!({
+        "type": "object",
+        "required": ["succ", "code", "message"],
+        "properties": {
+            "succ": { "type": "boolean" },
+            "code": {
+                "type": "string",
+                "enum": ApiError::ALL.iter().map(|e| e.code()).collect::<Vec<_>>(),
+            },
+            "message": { "type": "string" },
+            "reason": { "type": "string", "description": "具体的失败原因，部分接口提供" },
+        },
+    });
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "draw-guess",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": schemas },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeSet, HashMap};
+
+    use super::routes;
+
+    /// 从 app.rs 源码中解析出 (版本, 方法, 路径)
+    fn registered_routes() -> BTreeSet<(String, String, String)> {
+        let source = include_str!("app.rs");
+        // 处理函数名 => (方法, 路径)
+        let mut handlers = HashMap::new();
+        let mut pending = None;
+        let mut in_v2 = false;
+        for line in source.lines() {
+            let line = line.trim();
+            if line == "mod v2 {" {
+                in_v2 = true;
+            }
+            for method in ["get", "post"].iter() {
+                let prefix = format!("#[{}(\"", method);
+                if let Some(rest) = line.strip_prefix(prefix.as_str()) {
+                    let path = rest.trim_end_matches("\")]").to_string();
+                    pending = Some((method.to_string(), path));
+                }
+            }
+            if let Some(index) = line.find("async fn ") {
+                let name = line[index + 9..].split('(').next().unwrap_or_default();
+                if let Some(route) = pending.take() {
+                    let name = if in_v2 { format!("v2::{}", name) } else { name.to_string() };
+                    handlers.insert(name, route);
+                }
+            }
+        }
+        let v1_start = source.find("web::scope(\"/v1\")").expect("v1 scope");
+        let v2_start = source.find("web::scope(\"/v2\")").expect("v2 scope");
+        // v1 到包裹 v2 scope 的 `.service(` 为止
+        let v1_end = source[..v2_start].rfind(".service(").expect("v2 scope service");
+        let v2_end = source[v2_start..].find(".bind(").map(|i| v2_start + i).expect("v2 scope end");
+        let mut set = BTreeSet::new();
+        for (version, block) in [("v1", &source[v1_start..v1_end]), ("v2", &source[v2_start..v2_end])].iter() {
+            for line in block.lines() {
+                if let Some(rest) = line.trim().strip_prefix(".service(") {
+                    let name = rest.split(')').next().unwrap_or_default();
+                    let (method, path) = handlers.get(name)
+                        .unwrap_or_else(|| panic!("handler {} has no route attribute", name));
+                    set.insert((version.to_string(), method.clone(), path.clone()));
+                }
+            }
+        }
+        set
+    }
+
+    #[test]
+    fn spec_matches_handlers() {
+        let documented = routes().iter()
+            .flat_map(|route| route.versions.iter().map(move |version| {
+                (version.to_string(), route.method.to_string(), route.path.to_string())
+            }))
+            .collect::<BTreeSet<_>>();
+        let registered = registered_routes();
+        let missing = registered.difference(&documented).collect::<Vec<_>>();
+        let stale = documented.difference(&registered).collect::<Vec<_>>();
+        assert!(missing.is_empty(), "routes missing from openapi spec: {:?}", missing);
+        assert!(stale.is_empty(), "openapi spec documents unknown routes: {:?}", stale);
+
+        let spec = super::spec();
+        let parameters = spec["paths"]["/v1/draw"]["get"]["parameters"].as_array().unwrap();
+        assert!(parameters.iter().any(|p| p["name"] == "rawData" && p["in"] == "query"));
+        assert!(spec["components"]["schemas"]["DrawOp"].is_object());
+    }
+}
@@ -1,9 +1,7 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
-use crate::wrap::WrapToValue;
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Player {
     pub user_id: String,
@@ -11,9 +9,14 @@ pub struct Player {
     pub nick_name: String,
 }
 
-
-impl WrapToValue for Player {
-    fn to_value(&self) -> Value {
-        serde_json::to_value(self).unwrap_or_default()
+#[cfg(test)]
+impl Player {
+    /// 测试用的玩家，头像与昵称都是 id
+    pub fn test(id: &str) -> Self {
+        Player {
+            user_id: id.to_string(),
+            avatar_url: id.to_string(),
+            nick_name: id.to_string(),
+        }
     }
-}
\ No newline at end of file
+}
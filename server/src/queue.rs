@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{player::Player, room::{MAX_PEOPLE, MIN_START_PEOPLE}};
+
+/// 收集排队玩家的时间窗口（秒）
+pub const QUEUE_WINDOW: u8 = 5;
+/// 超过这么久没有查询排队状态的玩家移出队列（秒）
+pub const QUEUE_ENTRY_TIMEOUT: i64 = 15;
+
+/// 排队中的玩家
+#[derive(Debug, Clone)]
+pub struct QueueEntry {
+    pub player: Player,
+    pub language: String,
+    /// 最后一次加入或查询排队状态的时间
+    pub last_seen: i64,
+}
+
+impl QueueEntry {
+    /// 客户端不再轮询，视为已经离开
+    pub fn is_stale(&self, now: i64) -> bool {
+        now - self.last_seen > QUEUE_ENTRY_TIMEOUT * 1000
+    }
+}
+
+/// 排队状态
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum QueueStatus {
+    /// 不在队列中
+    Idle,
+    /// 等待中，`position` 从 1 开始
+    Waiting { position: usize, size: usize },
+    /// 已分配房间
+    Matched {
+        #[serde(rename = "roomId")]
+        room_id: String,
+    },
+}
+
+/// 把 `count` 个人尽量平均地分成若干组，每组不超过 [`MAX_PEOPLE`]
+fn group_sizes(count: usize) -> Vec<usize> {
+    if count < MIN_START_PEOPLE as usize {
+        return vec![]
+    }
+    let groups = (count + MAX_PEOPLE - 1) / MAX_PEOPLE;
+    (0..groups)
+        .map(|i| count / groups + usize::from(i < count % groups))
+        .collect()
+}
+
+/// 按语言分组并切分成房间，人数不够开局的留在队列中
+pub fn form_rooms(entries: Vec<QueueEntry>) -> (Vec<Vec<QueueEntry>>, Vec<QueueEntry>) {
+    let mut by_language = BTreeMap::<String, Vec<QueueEntry>>::new();
+    for entry in entries {
+        by_language.entry(entry.language.clone()).or_default().push(entry);
+    }
+    let mut rooms = vec![];
+    let mut rest = vec![];
+    for (_, mut entries) in by_language {
+        let sizes = group_sizes(entries.len());
+        if sizes.is_empty() {
+            rest.extend(entries);
+            continue
+        }
+        for size in sizes {
+            let remain = entries.split_off(size);
+            rooms.push(entries);
+            entries = remain;
+        }
+    }
+    (rooms, rest)
+}
+
+#[test]
+fn test_form_rooms() {
+    assert_eq!(group_sizes(2), Vec::<usize>::new());
+    assert_eq!(group_sizes(3), vec![3]);
+    assert_eq!(group_sizes(6), vec![6]);
+    assert_eq!(group_sizes(7), vec![4, 3]);
+    assert_eq!(group_sizes(13), vec![5, 4, 4]);
+
+    let entry = |id: usize, language: &str| QueueEntry {
+        player: Player::test(&id.to_string()),
+        language: language.to_string(),
+        last_seen: 0,
+    };
+    let mut entries = (0..7).map(|id| entry(id, "zh")).collect::<Vec<_>>();
+    entries.push(entry(7, "en"));
+    entries.push(entry(8, "en"));
+    let (rooms, rest) = form_rooms(entries);
+    assert_eq!(rooms.iter().map(|room| room.len()).collect::<Vec<_>>(), vec![4, 3]);
+    // 先排队的先进房间
+    assert_eq!(rooms[0][0].player.user_id, "0");
+    assert_eq!(rest.len(), 2);
+    assert!(rest.iter().all(|entry| entry.language == "en"));
+
+    assert!(!rest[0].is_stale(QUEUE_ENTRY_TIMEOUT * 1000));
+    assert!(rest[0].is_stale(QUEUE_ENTRY_TIMEOUT * 1000 + 1));
+}
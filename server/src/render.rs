@@ -0,0 +1,351 @@
+use crate::draw_data::{parse_color, DrawData, DrawOp, PathStroke, Point, CANVAS_SIZE};
+
+/// 导出图片的最小边长
+pub const MIN_RENDER_SIZE: u32 = 16;
+/// 导出图片的最大边长
+pub const MAX_RENDER_SIZE: u32 = 2048;
+/// 每回合缩略图的边长
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+type Rgb = [u8; 3];
+
+/// 画布导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasFormat {
+    Png,
+    Svg,
+}
+
+impl Default for CanvasFormat {
+    fn default() -> Self {
+        CanvasFormat::Png
+    }
+}
+
+/// 从房间复制出来的画布，在 GameActor 之外渲染
+#[derive(Debug, Clone)]
+pub struct CanvasSource {
+    pub draw_data: DrawData,
+    pub background: String,
+}
+
+impl CanvasSource {
+    /// 按格式编码，png 需要回放全部笔画，svg 需要计算油漆桶区域
+    pub fn encode(&self, format: CanvasFormat, size: u32) -> Option<Vec<u8>> {
+        match format {
+            CanvasFormat::Png => render_png(&self.draw_data, &self.background, size),
+            CanvasFormat::Svg => Some(self.draw_data.to_svg(&self.background, size).into_bytes()),
+        }
+    }
+}
+
+/// 颜色解析失败时使用黑色
+const FALLBACK_COLOR: Rgb = [0, 0, 0];
+
+/// 内存中的 RGB 画布，坐标按前端画布尺寸等比缩放
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    scale: f64,
+    background: Rgb,
+    pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    pub fn new(size: u32, background: &str) -> Self {
+        let size = clamp_size(size);
+        let background = parse_color(background).unwrap_or([255, 255, 255]);
+        Self {
+            width: size,
+            height: size,
+            scale: size as f64 / CANVAS_SIZE as f64,
+            background,
+            pixels: vec![background; (size * size) as usize],
+        }
+    }
+
+    /// 把一个绘制操作画到画布上
+    pub fn apply(&mut self, op: &DrawOp) {
+        match op {
+            DrawOp::Path { data } => {
+                if let Some(stroke) = PathStroke::parse(data) {
+                    let color = to_rgb(&stroke.color);
+                    self.polyline(&stroke.points, stroke.width, color);
+                }
+            },
+            DrawOp::Line { from, to, color, width } => {
+                self.polyline(&[*from, *to], *width, to_rgb(color));
+            },
+            DrawOp::Rect { from, to, color, width, fill } => {
+                let (x0, y0) = self.map(from);
+                let (x1, y1) = self.map(to);
+                if let Some(fill) = fill {
+                    let fill = to_rgb(fill);
+                    self.fill_where(x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1), |_, _| true, fill);
+                }
+                let corners = [
+                    *from,
+                    Point { x: to.x, y: from.y },
+                    *to,
+                    Point { x: from.x, y: to.y },
+                    *from,
+                ];
+                self.polyline(&corners, *width, to_rgb(color));
+            },
+            DrawOp::Ellipse { from, to, color, width, fill } => {
+                let (x0, y0) = self.map(from);
+                let (x1, y1) = self.map(to);
+                let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+                let (rx, ry) = (((x1 - x0) / 2.0).abs(), ((y1 - y0) / 2.0).abs());
+                if let Some(fill) = fill {
+                    if rx > 0.0 && ry > 0.0 {
+                        let fill = to_rgb(fill);
+                        self.fill_where(cx - rx, cy - ry, cx + rx, cy + ry, |x, y| {
+                            let (dx, dy) = ((x - cx) / rx, (y - cy) / ry);
+                            dx * dx + dy * dy <= 1.0
+                        }, fill);
+                    }
+                }
+                let steps = ((rx + ry) * 2.0).max(16.0) as usize;
+                let outline = (0..=steps).map(|i| {
+                    let t = i as f64 / steps as f64 * std::f64::consts::PI * 2.0;
+                    (cx + rx * t.cos(), cy + ry * t.sin())
+                }).collect::<Vec<_>>();
+                let radius = self.radius(*width);
+                let color = to_rgb(color);
+                for pair in outline.windows(2) {
+                    self.segment(pair[0], pair[1], radius, color);
+                }
+            },
+            DrawOp::Fill { at, color } => {
+                let (x, y) = self.map(at);
+                self.flood_fill(x as i64, y as i64, to_rgb(color));
+            },
+            DrawOp::Eraser { points, width } => {
+                let background = self.background;
+                self.polyline(points, *width, background);
+            },
+        }
+    }
+
+    /// 原始 RGB 数据
+    pub fn rgb(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|p| p.iter().copied()).collect()
+    }
+
+    /// 编码为 png
+    pub fn to_png(&self) -> Option<Vec<u8>> {
+        let mut buffer = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut buffer, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().ok()?;
+            writer.write_image_data(&self.rgb()).ok()?;
+        }
+        Some(buffer)
+    }
+
+    fn map(&self, p: &Point) -> (f64, f64) {
+        (p.x as f64 * self.scale, p.y as f64 * self.scale)
+    }
+
+    fn radius(&self, width: u16) -> f64 {
+        (width as f64 * self.scale / 2.0).max(0.5)
+    }
+
+    fn polyline(&mut self, points: &[Point], width: u16, color: Rgb) {
+        let radius = self.radius(width);
+        match points {
+            [] => (),
+            [p] => {
+                let p = self.map(p);
+                self.segment(p, p, radius, color);
+            },
+            _ => {
+                for pair in points.windows(2) {
+                    let (a, b) = (self.map(&pair[0]), self.map(&pair[1]));
+                    self.segment(a, b, radius, color);
+                }
+            },
+        }
+    }
+
+    /// 画一段带圆角的粗线段
+    fn segment(&mut self, a: (f64, f64), b: (f64, f64), radius: f64, color: Rgb) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len2 = dx * dx + dy * dy;
+        let r2 = radius * radius;
+        self.fill_where(
+            a.0.min(b.0) - radius, a.1.min(b.1) - radius,
+            a.0.max(b.0) + radius, a.1.max(b.1) + radius,
+            |x, y| {
+                let t = if len2 == 0.0 {
+                    0.0
+                } else {
+                    (((x - a.0) * dx + (y - a.1) * dy) / len2).max(0.0).min(1.0)
+                };
+                let (px, py) = (a.0 + t * dx - x, a.1 + t * dy - y);
+                px * px + py * py <= r2
+            },
+            color,
+        );
+    }
+
+    /// 在包围盒内，对满足条件的像素（以像素中心判断）着色
+    fn fill_where<F>(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, inside: F, color: Rgb)
+    where
+        F: Fn(f64, f64) -> bool,
+    {
+        let clamp_x = |v: f64| v.max(0.0).min(self.width as f64 - 1.0) as u32;
+        let clamp_y = |v: f64| v.max(0.0).min(self.height as f64 - 1.0) as u32;
+        if x1 < 0.0 || y1 < 0.0 || x0 >= self.width as f64 || y0 >= self.height as f64 {
+            return
+        }
+        let (xs, xe, ys, ye) = (clamp_x(x0.floor()), clamp_x(x1.ceil()), clamp_y(y0.floor()), clamp_y(y1.ceil()));
+        for y in ys..=ye {
+            for x in xs..=xe {
+                if inside(x as f64 + 0.5, y as f64 + 0.5) {
+                    self.pixels[(y * self.width + x) as usize] = color;
+                }
+            }
+        }
+    }
+
+    /// 把起点限制在画布内
+    fn clamp_point(&self, x: i64, y: i64) -> (i64, i64) {
+        (x.max(0).min(self.width as i64 - 1), y.max(0).min(self.height as i64 - 1))
+    }
+
+    /// 油漆桶：把与起点颜色相同且相连的区域替换为目标颜色，直接在画布上填充，不分配掩码
+    fn flood_fill(&mut self, x: i64, y: i64, color: Rgb) {
+        let (x, y) = self.clamp_point(x, y);
+        let target = self.pixels[(y * self.width as i64 + x) as usize];
+        if target == color {
+            return
+        }
+        let (w, h) = (self.width as i64, self.height as i64);
+        scanline_fill(w, h, x, y, &mut self.pixels, |pixels, i| pixels[i] == target, |pixels, i| pixels[i] = color);
+    }
+
+    /// 计算与起点颜色相同且相连的区域，返回与像素一一对应的掩码
+    fn flood_region(&self, x: i64, y: i64) -> Vec<bool> {
+        let (x, y) = self.clamp_point(x, y);
+        let (w, h) = (self.width as i64, self.height as i64);
+        let pixels = &self.pixels;
+        let target = pixels[(y * w + x) as usize];
+        let mut region = vec![false; pixels.len()];
+        scanline_fill(w, h, x, y, &mut region, |region, i| !region[i] && pixels[i] == target, |region, i| region[i] = true);
+        region
+    }
+
+    /// 油漆桶在画布坐标系下会覆盖的区域，按行合并为 `(y, x, 长度)`
+    pub fn fill_runs(&self, at: &Point) -> Vec<(u32, u32, u32)> {
+        let (x, y) = self.map(at);
+        let region = self.flood_region(x as i64, y as i64);
+        let mut runs = vec![];
+        for (y, row) in region.chunks(self.width as usize).enumerate() {
+            let mut start = None;
+            for (x, inside) in row.iter().chain(std::iter::once(&false)).enumerate() {
+                match (start, *inside) {
+                    (None, true) => start = Some(x),
+                    (Some(s), false) => {
+                        runs.push((y as u32, s as u32, (x - s) as u32));
+                        start = None;
+                    },
+                    _ => (),
+                }
+            }
+        }
+        runs
+    }
+}
+
+/// 扫描线填充：从种子向左右扩展成一段，上下两行中每段可填充的像素只压入一个种子。
+/// 填充过的像素必须不再满足 `fillable`
+fn scanline_fill<T, P, F>(w: i64, h: i64, x: i64, y: i64, state: &mut T, fillable: P, fill: F)
+where
+    P: Fn(&T, usize) -> bool,
+    F: Fn(&mut T, usize),
+{
+    let index = |x: i64, y: i64| (y * w + x) as usize;
+    let mut stack = vec![(x, y)];
+    while let Some((x, y)) = stack.pop() {
+        if !fillable(state, index(x, y)) {
+            continue
+        }
+        let mut left = x;
+        while left > 0 && fillable(state, index(left - 1, y)) {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < w && fillable(state, index(right + 1, y)) {
+            right += 1;
+        }
+        for x in left..=right {
+            fill(state, index(x, y));
+        }
+        for ny in [y - 1, y + 1].iter().copied().filter(|ny| *ny >= 0 && *ny < h) {
+            let mut in_span = false;
+            for x in left..=right {
+                let open = fillable(state, index(x, ny));
+                if open && !in_span {
+                    stack.push((x, ny));
+                }
+                in_span = open;
+            }
+        }
+    }
+}
+
+/// 把请求的边长限制在允许范围内
+pub fn clamp_size(size: u32) -> u32 {
+    size.max(MIN_RENDER_SIZE).min(MAX_RENDER_SIZE)
+}
+
+fn to_rgb(color: &str) -> Rgb {
+    parse_color(color).unwrap_or(FALLBACK_COLOR)
+}
+
+/// 按顺序回放所有笔画得到画布
+pub fn render(draw_data: &DrawData, background: &str, size: u32) -> Canvas {
+    let mut canvas = Canvas::new(size, background);
+    for unit in draw_data.slice(0) {
+        canvas.apply(&unit.op);
+    }
+    canvas
+}
+
+/// 回放笔画并编码为 png
+pub fn render_png(draw_data: &DrawData, background: &str, size: u32) -> Option<Vec<u8>> {
+    render(draw_data, background, size).to_png()
+}
+
+#[test]
+fn test_render() {
+    let mut dd = DrawData::default();
+    dd.draw_op(DrawOp::Rect {
+        from: Point { x: 100, y: 100 },
+        to: Point { x: 400, y: 400 },
+        color: "#000000".into(),
+        width: 4,
+        fill: None,
+    }, 1);
+    dd.draw_op(DrawOp::Fill { at: Point { x: 250, y: 250 }, color: "#ff0000".into() }, 2);
+    let canvas = render(&dd, "#ffffff", 100);
+    let pixel = |x: u32, y: u32| canvas.pixels[(y * canvas.width + x) as usize];
+    assert_eq!(pixel(50, 50), [255, 0, 0]);
+    assert_eq!(pixel(5, 5), [255, 255, 255]);
+    assert_eq!(pixel(20, 50), [0, 0, 0]);
+    let png = canvas.to_png().unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+
+    // 直接填充与掩码得到的区域一致
+    let mut filled = render(&dd, "#ffffff", 100);
+    let region = filled.flood_region(5, 5);
+    filled.flood_fill(5, 5, [0, 0, 255]);
+    for (pixel, inside) in filled.pixels.iter().zip(region) {
+        assert_eq!(*pixel == [0, 0, 255], inside);
+    }
+}
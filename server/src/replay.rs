@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{draw_data::{DrawData, DrawOp}, gallery::{Guess, RoundRecord}};
+
+/// 回放文件格式版本
+pub const REPLAY_VERSION: u32 = 1;
+
+/// 回合内发生的事件，时间戳为服务端收到的时间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundEvent {
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub action: RoundAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RoundAction {
+    /// 绘制
+    Draw { op: DrawOp },
+    /// 撤销上一笔
+    Undo,
+    /// 清空画布
+    Clear,
+    /// 修改画布背景色
+    Background { color: String },
+    /// 聊天（答案已被屏蔽）
+    Chat {
+        #[serde(rename = "userId")]
+        user_id: String,
+        content: String,
+    },
+    /// 得分
+    Score {
+        #[serde(rename = "userId")]
+        user_id: String,
+        score: u16,
+    },
+}
+
+impl RoundEvent {
+    pub fn now(action: RoundAction) -> Self {
+        Self {
+            timestamp: chrono::Local::now().timestamp_millis(),
+            action,
+        }
+    }
+}
+
+/// 时间轴上的一项，`offset` 为相对开始绘制的毫秒数
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimelineEntry {
+    pub offset: i64,
+    #[serde(flatten)]
+    pub action: RoundAction,
+}
+
+/// 一个回合的回放，可导出为独立的 json 文件并重新导入
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    pub version: u32,
+    pub round: usize,
+    pub word: String,
+    pub drawer_id: String,
+    pub drawer_name: Option<String>,
+    /// 回合时长（毫秒）
+    pub duration: i64,
+    pub guessers: Vec<Guess>,
+    pub scores: HashMap<String, u16>,
+    pub timeline: Vec<TimelineEntry>,
+}
+
+impl Replay {
+    pub fn from_record(record: &RoundRecord) -> Self {
+        let mut timeline = record.events.iter()
+            .map(|event| TimelineEntry {
+                offset: (event.timestamp - record.started_at).max(0),
+                action: event.action.clone(),
+            })
+            .collect::<Vec<_>>();
+        timeline.sort_by_key(|entry| entry.offset);
+        Self {
+            version: REPLAY_VERSION,
+            round: record.round,
+            word: record.word.clone(),
+            drawer_id: record.drawer_id.clone(),
+            drawer_name: record.drawer_name.clone(),
+            duration: (record.ended_at - record.started_at).max(0),
+            guessers: record.guessers.clone(),
+            scores: record.scores.clone(),
+            timeline,
+        }
+    }
+
+    /// 导入回放文件，校验版本与绘制数据
+    pub fn import(json: &str) -> Result<Self, String> {
+        let mut replay = serde_json::from_str::<Replay>(json).map_err(|e| e.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("unsupported replay version {}", replay.version))
+        }
+        if replay.timeline.iter().any(|entry| entry.offset < 0) {
+            return Err("negative offset in timeline".to_string())
+        }
+        let invalid_op = replay.timeline.iter().any(|entry| match &entry.action {
+            RoundAction::Draw { op } => !op.validate(),
+            _ => false,
+        });
+        if invalid_op {
+            return Err("invalid draw operation in timeline".to_string())
+        }
+        replay.timeline.sort_by_key(|entry| entry.offset);
+        Ok(replay)
+    }
+
+    /// 某一时刻画布上可见的笔画和背景色
+    pub fn canvas_at(&self, offset: i64) -> (DrawData, String) {
+        let mut draw_data = DrawData::default();
+        let mut background = "#ffffff".to_string();
+        for entry in self.timeline.iter().take_while(|entry| entry.offset <= offset) {
+            match &entry.action {
+                RoundAction::Draw { op } => {
+                    draw_data.draw_op(op.clone(), entry.offset);
+                },
+                RoundAction::Undo => draw_data.undo(),
+                RoundAction::Clear => draw_data.clear(),
+                RoundAction::Background { color } => background = color.clone(),
+                _ => (),
+            }
+        }
+        (draw_data, background)
+    }
+}
+
+#[test]
+fn test_replay_round_trip() {
+    use crate::draw_data::Point;
+
+    let line = |x: i32| RoundAction::Draw {
+        op: DrawOp::Line {
+            from: Point { x, y: 0 },
+            to: Point { x, y: 100 },
+            color: "#000000".into(),
+            width: 2,
+        },
+    };
+    let event = |timestamp: i64, action: RoundAction| RoundEvent { timestamp, action };
+    let record = RoundRecord {
+        round: 0,
+        word: "苹果".into(),
+        drawer_id: "1".into(),
+        drawer_name: Some("1".into()),
+        background: "#ffffff".into(),
+        draw_list: vec![],
+        guessers: vec![],
+        scores: HashMap::new(),
+        events: vec![
+            event(1000, RoundAction::Background { color: "#ffffff".into() }),
+            event(1500, line(10)),
+            event(2000, line(20)),
+            event(2500, RoundAction::Undo),
+            event(2600, RoundAction::Chat { user_id: "2".into(), content: "**".into() }),
+            event(2600, RoundAction::Score { user_id: "2".into(), score: 6 }),
+        ],
+        started_at: 1000,
+        ended_at: 21000,
+    };
+    let replay = Replay::from_record(&record);
+    assert_eq!(replay.duration, 20000);
+    assert_eq!(replay.timeline[1].offset, 500);
+    assert_eq!(replay.canvas_at(1000).0.len(), 2);
+    assert_eq!(replay.canvas_at(1500).0.len(), 1);
+
+    let json = serde_json::to_string(&replay).unwrap();
+    let imported = Replay::import(&json).unwrap();
+    assert_eq!(imported.timeline.len(), 6);
+    assert!(Replay::import(&json.replace("\"version\":1", "\"version\":2")).is_err());
+}
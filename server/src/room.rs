@@ -1,13 +1,16 @@
 use std::{collections::{HashMap, HashSet}, ops::{AddAssign}};
-use serde_json::{Value, json};
+use rand::Rng;
 use uuid::Uuid;
-use crate::{player::Player, wrap::{PollingProvider, WrapToValue}, draw_data::DrawData};
+use crate::{player::Player, draw_data::{DrawData, DrawOp, is_valid_color}, gallery::{Guess, RoundRecord}, replay::{RoundAction, RoundEvent}, seat_queue::SeatOffer, spectator::SpectatorGuess, late_join::{LateJoinScore, MidGameJoin}, team::{GuessKind, Team, STEAL_DELAY, STEAL_SCORE, TEAMMATE_BONUS}, telephone::TelephoneGame, custom_words::CUSTOM_WORD_RATIO, vote::Vote, error::{ApiError, ApiResult}};
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use unicode_segmentation::UnicodeSegmentation;
 
-static MAX_CHAT_HISTORY: usize = 20;
+pub(crate) static MAX_CHAT_HISTORY: usize = 20;
 /// 最大人数
 pub const MAX_PEOPLE: usize = 6;
+/// 一个回合（或传话模式一张画）最多的笔画数，限制渲染与回放的开销
+pub const MAX_DRAW_UNITS: usize = 2000;
 /// 最小开局人数
 pub const MIN_START_PEOPLE: u8 = 3;
 
@@ -17,11 +20,26 @@ pub const CHOOSE_TIME: u8 = 10;
 pub const DRAW_TIME: u8 = 20;
 /// 回合结束的时间
 pub const RESULT_TIME: u8 = 5;
+/// 默认语言
+pub const DEFAULT_LANGUAGE: &str = "zh";
+/// 默认词库
+pub const DEFAULT_WORD_PACK: &str = "default";
+/// 默认最多观战人数
+pub const MAX_OBSERVERS: u8 = 20;
+/// 房主强制开始的最少人数
+pub const MIN_FORCE_START_PEOPLE: usize = 2;
+/// 房间名最大长度（字符）
+pub const MAX_ROOM_NAME_LEN: usize = 20;
 
-static DEFAULT_COLOR: &'static str = "#ffffff";
+pub(crate) static DEFAULT_COLOR: &'static str = "#ffffff";
+
+/// 邀请码长度
+pub const INVITE_CODE_LEN: usize = 6;
+/// 邀请码字符集，去掉了容易混淆的 0/O、1/I
+const INVITE_CODE_CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
 
 /// 房间数据
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RoomData {
     /// 房间id
@@ -30,6 +48,36 @@ pub struct RoomData {
     /// 房间名字
     #[serde(skip_serializing)]
     pub name: String,
+    /// 私密房间不出现在房间列表中，也不会被随机匹配到
+    #[serde(skip)]
+    pub private: bool,
+    /// 私密房间的密码
+    #[serde(skip)]
+    pub password: Option<String>,
+    /// 私密房间的邀请码
+    #[serde(skip)]
+    pub invite_code: Option<String>,
+    /// 房主id，房主离开后由最早进入房间的玩家接任
+    pub owner: Option<String>,
+    /// 玩家进入房间的顺序
+    #[serde(skip)]
+    pub join_order: Vec<String>,
+    /// 被房主封禁的玩家，房间存在期间不能再加入
+    #[serde(skip)]
+    pub banned: HashSet<String>,
+    /// 被房主锁定的座位
+    pub locked_seats: [bool; MAX_PEOPLE],
+    /// 房间设置
+    pub settings: RoomSettings,
+    /// 正在进行的投票
+    #[serde(skip)]
+    pub vote: Option<Vote>,
+    /// 投票序号
+    #[serde(skip)]
+    pub vote_seq: u32,
+    /// 每个玩家上次发起投票的时间
+    #[serde(skip)]
+    pub vote_cooldown: HashMap<String, i64>,
     /// 房间玩家 key: user_id
     pub players: HashMap<String, Player>,
     /// 座位, id列表， None 表示没人
@@ -38,8 +86,40 @@ pub struct RoomData {
     pub ready_state: HashMap<String, bool>,
     /// 观战列表
     pub observer: HashSet<String>,
+    /// 团队模式下玩家的队伍
+    pub teams: HashMap<String, Team>,
+    /// 团队模式下两队交替的画图顺序，开局时生成
+    #[serde(skip)]
+    pub team_order: Vec<usize>,
+    /// 进行中的传话游戏
+    #[serde(skip)]
+    pub telephone: Option<TelephoneGame>,
+    /// 传话游戏的步骤序号
+    #[serde(skip)]
+    pub telephone_seq: u32,
+    /// 协作画板的笔画版本，中间的笔画被移除时递增
+    #[serde(skip)]
+    pub draw_revision: u32,
+    /// 房主上传的自定义词库
+    #[serde(skip)]
+    pub custom_words: Vec<String>,
+    /// 排队等座位的观战玩家，先到先得
+    #[serde(skip)]
+    pub seat_queue: Vec<String>,
+    /// 正在留给排队玩家的座位
+    #[serde(skip)]
+    pub seat_offer: Option<SeatOffer>,
+    /// 留座序号
+    #[serde(skip)]
+    pub seat_offer_seq: u32,
     /// 聊天历史 最多保存 [`MAX_CHAT_HISTORY`] 条
     pub chat_history: Vec<ChatItem>,
+    /// 观战频道的聊天，绘制阶段对座位上的玩家不可见
+    #[serde(skip)]
+    pub spectator_chat: Vec<ChatItem>,
+    /// 本回合观战玩家的跟猜记录，不计分
+    #[serde(skip)]
+    pub spectator_guesses: Vec<SpectatorGuess>,
     /// 游戏阶段
     pub stage: GameStage,
     /// 当前回合玩家座位
@@ -59,6 +139,18 @@ pub struct RoomData {
     pub round_score_map: HashMap<String, u16>,
     /// 结算数据
     pub settlement: HashMap<String, u16>,
+    /// 本回合猜对的记录，按顺序排列
+    #[serde(skip)]
+    pub round_guesses: Vec<Guess>,
+    /// 本回合发生的事件，用于回放
+    #[serde(skip)]
+    pub round_events: Vec<RoundEvent>,
+    /// 本回合开始绘制的时间
+    #[serde(skip)]
+    pub draw_started_at: Option<i64>,
+    /// 本局每一回合的存档，下一局开始时清空
+    #[serde(skip)]
+    pub gallery: Vec<RoundRecord>,
 }
 
 impl RoomData {
@@ -66,11 +158,33 @@ impl RoomData {
         Self {
             id: Uuid::new_v4().to_string(),
             name: "默认房间".to_owned(),
+            private: false,
+            password: None,
+            invite_code: None,
+            owner: None,
+            join_order: vec![],
+            banned: HashSet::new(),
+            locked_seats: Default::default(),
+            settings: Default::default(),
+            vote: None,
+            vote_seq: 0,
+            vote_cooldown: HashMap::new(),
             players: HashMap::new(),
             seat: Default::default(),
             observer: HashSet::new(),
+            teams: HashMap::new(),
+            team_order: vec![],
+            telephone: None,
+            telephone_seq: 0,
+            draw_revision: 0,
+            custom_words: vec![],
+            seat_queue: vec![],
+            seat_offer: None,
+            seat_offer_seq: 0,
             ready_state: HashMap::new(),
             chat_history: vec![],
+            spectator_chat: vec![],
+            spectator_guesses: vec![],
             stage: GameStage::Ready,
             cur_seat: None,
             cur_id: None,
@@ -80,16 +194,60 @@ impl RoomData {
             background: DEFAULT_COLOR.to_string(),
             round_score_map: Default::default(),
             settlement: Default::default(),
+            round_guesses: vec![],
+            round_events: vec![],
+            draw_started_at: None,
+            gallery: vec![],
+        }
+    }
+    /// 创建私密房间，没有密码时只能凭邀请码加入
+    pub fn new_private(password: Option<String>, invite_code: String) -> Self {
+        Self {
+            private: true,
+            password,
+            invite_code: Some(invite_code),
+            ..Self::new()
+        }
+    }
+    /// 私密房间需要密码或邀请码其一正确
+    pub fn check_access(&self, password: Option<&str>, invite_code: Option<&str>) -> ApiResult<()> {
+        if !self.private {
+            return Ok(())
+        }
+        let password_ok = matches!((self.password.as_deref(), password), (Some(expected), Some(password)) if expected == password);
+        let code_ok = matches!((self.invite_code.as_deref(), invite_code), (Some(expected), Some(code)) if expected.eq_ignore_ascii_case(code.trim()));
+        if password_ok || code_ok {
+            Ok(())
+        } else {
+            Err(ApiError::WrongPassword)
         }
     }
     pub fn can_join(&self) -> bool {
-        self.stage == GameStage::Ready && !self.seat.iter().all(|a| a.is_some())
+        self.stage == GameStage::Ready && self.free_seat().is_some()
     } 
+    /// 第一个没人、没有锁定且没有留给排队玩家的座位
+    pub(crate) fn free_seat(&self) -> Option<usize> {
+        let reserved = self.reserved_seat();
+        self.seat.iter()
+            .zip(self.locked_seats.iter())
+            .enumerate()
+            .position(|(pos, (seat, locked))| seat.is_none() && !locked && reserved != Some(pos))
+    }
     pub fn join_player(&mut self, player: &Player) {
         let id = player.user_id.clone();
-        self.players.insert(id.clone(), player.clone());
-        if let Some(val) = self.seat.iter_mut().find(|val| val.is_none()) {
-            *val = Some(id.clone())
+        if self.players.insert(id.clone(), player.clone()).is_none() {
+            self.join_order.push(id.clone());
+        }
+        if self.owner.is_none() {
+            self.owner = Some(id.clone());
+        }
+        // 游戏中按设置决定能否坐下
+        if let Some(pos) = self.join_seat() {
+            self.seat[pos] = Some(id.clone());
+            if self.in_game() {
+                self.apply_late_score(&id);
+                self.assign_late_team(&id);
+            }
         } else {
             self.observer.insert(id);
         }
@@ -138,25 +296,39 @@ impl RoomData {
 
     pub fn start(&mut self) {
         self.reset_ready();
+        self.gallery.clear();
+        if self.settings.team_mode {
+            self.assign_teams();
+        }
         self.next_player_draw();
     }
     
     /// 轮到下一位玩家
     pub fn next_player(&mut self) -> bool {
-        for (index, id) in self.seat.iter().enumerate() {
-            if id.is_some() {
-                match self.cur_seat {
-                    Some(cur_pos) if cur_pos < index =>  {
-                        self.cur_seat = Some(index);
-                        self.cur_id = Some(id.as_ref().unwrap().clone());
-                        return true
-                    },
-                    None => {
-                        self.cur_seat = Some(index);
-                        self.cur_id = Some(id.as_ref().unwrap().clone());
-                        return true;
+        self.clear_skip_vote();
+        if self.settings.team_mode {
+            // 团队模式两队交替画图
+            if let Some(index) = self.next_team_seat() {
+                self.cur_seat = Some(index);
+                self.cur_id = self.seat[index].clone();
+                return true
+            }
+        } else {
+            for (index, id) in self.seat.iter().enumerate() {
+                if id.is_some() {
+                    match self.cur_seat {
+                        Some(cur_pos) if cur_pos < index =>  {
+                            self.cur_seat = Some(index);
+                            self.cur_id = Some(id.as_ref().unwrap().clone());
+                            return true
+                        },
+                        None => {
+                            self.cur_seat = Some(index);
+                            self.cur_id = Some(id.as_ref().unwrap().clone());
+                            return true;
+                        }
+                        _ => (),
                     }
-                    _ => (),
                 }
             }
         }
@@ -185,8 +357,8 @@ impl RoomData {
     pub fn reset_ready(&mut self) {
         self.ready_state.clear()
     }
-    /// 把玩家移动到观战列表
-    pub fn move_to_observer(&mut self, target_user_id: &String) {
+    /// 把玩家移动到观战列表，已经坐下的玩家起身不受观战人数限制
+    pub fn move_to_observer(&mut self, target_user_id: &String) -> ApiResult<()> {
         let that_seat = self.seat.iter_mut()
             .find(|s| s.as_deref() == Some(target_user_id.as_ref()));
         if let Some(the_seat) = that_seat {
@@ -194,15 +366,47 @@ impl RoomData {
             self.observer.insert(target_user_id.clone());
             self.ready_state.remove(target_user_id);
             self.cacnel_ready(target_user_id);
+            Ok(())
+        } else {
+            Err(ApiError::NotSeated)
         }
     }
     /// 绘画结束
     pub fn draw_end(&mut self) {
         if self.stage == GameStage::Drawing {
             self.stage = GameStage::Result;
-            self.score_to_settlement();
+            self.clear_skip_vote();
+            let mut scores = self.round_score_map.clone();
+            let drawer_score = self.score_to_settlement();
+            if let Some(cur_id) = &self.cur_id {
+                if drawer_score != 0 {
+                    scores.insert(cur_id.clone(), drawer_score);
+                }
+            }
+            self.archive_round(scores);
         }
     }
+    /// 把本回合存入画廊
+    fn archive_round(&mut self, scores: HashMap<String, u16>) {
+        let drawer_id = match &self.cur_id {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        let record = RoundRecord {
+            round: self.gallery.len(),
+            word: self.word.clone(),
+            drawer_name: self.players.get(&drawer_id).map(|p| p.nick_name.clone()),
+            drawer_id,
+            background: self.background.clone(),
+            draw_list: self.draw_data.slice(0).to_vec(),
+            guessers: self.round_guesses.drain(..).collect(),
+            scores,
+            events: self.round_events.drain(..).collect(),
+            started_at: self.draw_started_at.take().unwrap_or_default(),
+            ended_at: chrono::Local::now().timestamp_millis(),
+        };
+        self.gallery.push(record);
+    }
     /// 轮到下一位玩家绘制
     pub fn next_player_draw(&mut self) -> bool {
         if self.stage == GameStage::Result || self.stage == GameStage::Ready {
@@ -220,8 +424,9 @@ impl RoomData {
             false
         }
     }
-    /// 临时成绩算入总成绩
-    fn score_to_settlement(&mut self) {
+    /// 临时成绩算入总成绩，返回画图玩家本回合的得分
+    fn score_to_settlement(&mut self) -> u16 {
+        let mut add_score = 0;
         let mut count = 0;
         // 把临时分数算入结算数据 && 统计人数
         for (id, score) in self.round_score_map.iter() {
@@ -232,7 +437,6 @@ impl RoomData {
         }
         if let Some(cur_id) = &self.cur_id {
             let cur_id = cur_id.clone();
-            let mut add_score = 0;
             let play_user_count = self.seat.iter().filter(|&a| a.is_some()).count();
             let player = &self.players.get(&cur_id);
             let nick_name: Option<String> = if let Some(player) = player {
@@ -242,7 +446,12 @@ impl RoomData {
             };
             let answer_str = format!("答案是【{}】，", self.word);
             if let Some(nick_name) = nick_name {
-                if count == play_user_count - 1 {
+                if self.settings.team_mode {
+                    // 团队模式按答对的队友人数给画图玩家加分
+                    let mates = self.teammates_guessed(&cur_id);
+                    add_score = mates as u16 * TEAMMATE_BONUS;
+                    self.add_system_chat(format!("{}本轮{}名队友答对，{}名对手抢答，玩家{}得{}分", answer_str, mates, count - mates, nick_name, add_score));
+                } else if count == play_user_count - 1 {
                     self.add_system_chat(format!("{}本轮所有玩家答对，玩家{}不得分", answer_str, nick_name))
                 } else if count == play_user_count - 2 {
                     add_score = 6;
@@ -258,22 +467,36 @@ impl RoomData {
             }
             if add_score != 0 {
                 self.settlement.entry(cur_id.clone()).or_default().add_assign(add_score);
+                self.round_events.push(RoundEvent::now(RoundAction::Score { user_id: cur_id, score: add_score }));
             }
         }
         self.round_score_map.clear();
+        add_score
     }
     fn set_next_choose_time(&mut self) {
-        self.next_timestamp = Some(next_time(CHOOSE_TIME as i64));
+        self.next_timestamp = Some(next_time(self.settings.choose_time as i64));
     }
     /// 从观战列表点击座位坐下
-    pub fn sit_on(&mut self, target_user_id: &String, pos: usize) -> bool {
-        if pos >= MAX_PEOPLE { return false } // 越界
-        if self.seat[pos].is_some() { return false } // 位置已经有人
+    pub fn sit_on(&mut self, target_user_id: &String, pos: usize) -> ApiResult<()> {
+        if pos >= MAX_PEOPLE { return Err(ApiError::InvalidPayload) } // 越界
+        if self.seat[pos].is_some() { return Err(ApiError::SeatTaken) } // 位置已经有人
+        if self.locked_seats[pos] { return Err(ApiError::SeatLocked) } // 座位被锁定
+        if self.mid_game_join().map_or(false, |join| join != MidGameJoin::Seat) { return Err(ApiError::WrongStage) } // 游戏中不允许坐下
+        if self.in_game() && !self.turn_ahead(pos) { return Err(ApiError::WrongStage) } // 本局轮不到的座位
+        let offered_to = self.seat_offer.as_ref().filter(|offer| offer.pos == pos).map(|offer| &offer.user_id);
+        if offered_to.map_or(false, |id| id != target_user_id) { return Err(ApiError::SeatTaken) } // 座位留给了排队的玩家
         let is_ob = self.observer.remove(target_user_id); // 是否在观战列表中
         if is_ob {
             self.seat[pos] = Some(target_user_id.clone());
+            self.dequeue_seat(target_user_id);
+            if self.in_game() {
+                self.apply_late_score(target_user_id);
+                self.assign_late_team(target_user_id);
+            }
+            Ok(())
+        } else {
+            Err(ApiError::NotObserver)
         }
-        is_ob
     }
     pub fn add_chat(&mut self, user_id: String, content: String) {
         let mut content = content;
@@ -283,8 +506,15 @@ impl RoomData {
             user_id: user_id.clone(),
             timestamp: chrono::Local::now().timestamp_millis(),
         };
+        if self.stage == GameStage::Drawing {
+            self.round_events.push(RoundEvent::now(RoundAction::Chat {
+                user_id: item.user_id.clone(),
+                content: item.content.clone(),
+            }));
+        }
         self.chat_history.push(item);
         if let Some((no, add_score)) = result {
+            self.round_events.push(RoundEvent::now(RoundAction::Score { user_id: user_id.clone(), score: add_score }));
             self.chat_history.push(ChatItem {
                 content: format!("{}第{}个猜对了, 得{}分",  self.players.get(&user_id).unwrap().nick_name, no, add_score),
                 user_id: "0".to_string(),
@@ -296,7 +526,7 @@ impl RoomData {
             self.chat_history.drain(..self.chat_history.len() - MAX_CHAT_HISTORY);
         }
     }
-    fn add_system_chat(&mut self, content: String) {
+    pub fn add_system_chat(&mut self, content: String) {
         self.chat_history.push(ChatItem {
             content,
             user_id: "0".to_string(),
@@ -306,186 +536,464 @@ impl RoomData {
     fn check_answer(&mut self, user_id: &String, content: &mut String) -> Option<(usize, u16)> {
         if self.stage == GameStage::Drawing {
             if self.word.eq(content) { // 答对了
-                *content = "*".repeat(content.graphemes(true).count());
+                *content = mask_answer(content, &self.word);
                 if !self.is_current_player(user_id) { // 画图人说话不能加分
                     if self.round_score_map.get(user_id).is_none() {
                         let no = self.round_score_map.len() + 1;
-                        let add_score = match no {
-                            1 => 6, // 第一个回答，得6分
-                            2 => 5, // 第二个回答，得5分
-                            _ => 3, // 其他，得3分
+                        let add_score = match self.guess_kind(user_id, chrono::Local::now().timestamp_millis()) {
+                            GuessKind::Normal => match no {
+                                1 => 6, // 第一个回答，得6分
+                                2 => 5, // 第二个回答，得5分
+                                _ => 3, // 其他，得3分
+                            },
+                            GuessKind::Steal => STEAL_SCORE, // 对方队伍抢答
+                            GuessKind::TooEarly => return None, // 还不能抢答
                         };
                         self.round_score_map.insert(user_id.clone(), add_score);
+                        self.round_guesses.push(Guess {
+                            user_id: user_id.clone(),
+                            nick_name: self.players.get(user_id).map(|p| p.nick_name.clone()).unwrap_or_default(),
+                            order: no,
+                            score: add_score,
+                            timestamp: chrono::Local::now().timestamp_millis(),
+                        });
                         return Some((no, add_score));
                     }
                 }
             } else { // 没答对
                 // 把含有答案的字符串替换了
-                let new_content = content.replace(self.word.as_str(), "*".repeat(self.word.len()).as_str());
-                *content = new_content;
+                *content = mask_answer(content, &self.word);
             }
         }
         None
     }
-    /// 玩家退出房间
-    pub fn player_leave(&mut self, player_id: &String) {
+    /// 玩家退出房间，正在画图的玩家退出时结束其回合，返回被结束的回合所处的阶段
+    pub fn player_leave(&mut self, player_id: &String) -> Option<GameStage> {
         let item = self.seat.iter_mut().find(|a| a.as_deref() == Some(player_id.as_ref()));
         if let Some(pos) = item {
             *pos = None;
         } else {
             self.observer.remove(player_id);
         }
+        let ended = if self.cur_id.as_ref() == Some(player_id) {
+            self.end_turn()
+        } else {
+            None
+        };
         self.players.remove(player_id);
+        self.join_order.retain(|id| id != player_id);
+        self.dequeue_seat(player_id);
+        self.teams.remove(player_id);
+        // 房主离开，优先交给最早进入房间且有座位的玩家
+        if self.owner.as_deref() == Some(player_id.as_ref()) {
+            let seat = &self.seat;
+            self.owner = self.join_order.iter()
+                .find(|id| seat.iter().any(|s| s.as_ref() == Some(*id)))
+                .or_else(|| self.join_order.first())
+                .cloned();
+        }
+        ended
+    }
+    /// 结束当前玩家的回合，选词阶段直接轮到下一位，绘画阶段进入结算，返回结束时所处的阶段
+    pub fn end_turn(&mut self) -> Option<GameStage> {
+        match self.stage {
+            GameStage::Choose => {
+                self.choose_timeout();
+                Some(GameStage::Choose)
+            },
+            GameStage::Drawing => {
+                self.draw_end();
+                Some(GameStage::Drawing)
+            },
+            _ => None,
+        }
+    }
+    pub(crate) fn check_owner(&self, user_id: &String) -> ApiResult<()> {
+        if self.owner.as_deref() == Some(user_id.as_ref()) {
+            Ok(())
+        } else {
+            Err(ApiError::NotOwner)
+        }
+    }
+    /// 房主把玩家踢出房间，返回值同 `player_leave`
+    pub fn kick(&mut self, user_id: &String, target_id: &String) -> ApiResult<Option<GameStage>> {
+        self.check_owner(user_id)?;
+        if user_id == target_id {
+            return Err(ApiError::InvalidPayload)
+        }
+        if !self.players.contains_key(target_id) {
+            return Err(ApiError::NotInRoom)
+        }
+        Ok(self.player_leave(target_id))
+    }
+    /// 房主封禁玩家，在房间内则同时踢出，返回值同 `player_leave`
+    pub fn ban(&mut self, user_id: &String, target_id: &String) -> ApiResult<Option<GameStage>> {
+        self.check_owner(user_id)?;
+        if user_id == target_id {
+            return Err(ApiError::InvalidPayload)
+        }
+        self.banned.insert(target_id.clone());
+        if self.players.contains_key(target_id) {
+            Ok(self.player_leave(target_id))
+        } else {
+            Ok(None)
+        }
+    }
+    pub fn check_banned(&self, user_id: &String) -> ApiResult<()> {
+        if self.banned.contains(user_id) {
+            Err(ApiError::Banned)
+        } else {
+            Ok(())
+        }
+    }
+    /// 转让房主
+    pub fn transfer_owner(&mut self, user_id: &String, target_id: &String) -> ApiResult<()> {
+        self.check_owner(user_id)?;
+        if !self.players.contains_key(target_id) {
+            return Err(ApiError::NotInRoom)
+        }
+        self.owner = Some(target_id.clone());
+        Ok(())
+    }
+    /// 锁定/解锁座位，只能锁定空座位
+    pub fn lock_seat(&mut self, user_id: &String, pos: usize, locked: bool) -> ApiResult<()> {
+        self.check_owner(user_id)?;
+        if pos >= MAX_PEOPLE { return Err(ApiError::InvalidPayload) }
+        if locked && self.seat[pos].is_some() { return Err(ApiError::SeatTaken) }
+        self.locked_seats[pos] = locked;
+        // 锁定留给排队玩家的座位，把玩家放回队首
+        if locked && self.reserved_seat() == Some(pos) {
+            if let Some(offer) = self.seat_offer.take() {
+                self.seat_queue.insert(0, offer.user_id);
+            }
+        }
+        Ok(())
+    }
+    /// 修改房间设置，只能在准备阶段进行
+    pub fn update_settings(&mut self, user_id: &String, patch: SettingsPatch) -> ApiResult<()> {
+        self.check_owner(user_id)?;
+        if self.stage != GameStage::Ready {
+            return Err(ApiError::WrongStage)
+        }
+        let name = patch.name.map(|name| name.trim().to_string());
+        let invalid_name = name.as_ref()
+            .map_or(false, |name| name.is_empty() || name.chars().count() > MAX_ROOM_NAME_LEN);
+        let invalid_choose = patch.choose_time.map_or(false, |sec| !RoomSettings::CHOOSE_TIME_RANGE.contains(&sec));
+        let invalid_draw = patch.draw_time.map_or(false, |sec| !RoomSettings::DRAW_TIME_RANGE.contains(&sec));
+        let invalid_language = patch.language.as_deref().map_or(false, |language| !is_valid_tag(language));
+        let invalid_word_pack = patch.word_pack.as_deref().map_or(false, |word_pack| !is_valid_tag(word_pack));
+        let invalid_observers = patch.max_observers.map_or(false, |max| !RoomSettings::MAX_OBSERVERS_RANGE.contains(&max));
+        let invalid_steal = patch.steal_delay.map_or(false, |sec| !RoomSettings::STEAL_DELAY_RANGE.contains(&sec));
+        let invalid_ratio = patch.custom_word_ratio.map_or(false, |ratio| ratio > 100);
+        if invalid_name || invalid_choose || invalid_draw || invalid_language || invalid_word_pack || invalid_observers || invalid_steal || invalid_ratio {
+            return Err(ApiError::InvalidPayload)
+        }
+        if let Some(name) = name {
+            self.name = name;
+        }
+        if let Some(sec) = patch.choose_time {
+            self.settings.choose_time = sec;
+        }
+        if let Some(sec) = patch.draw_time {
+            self.settings.draw_time = sec;
+        }
+        if let Some(language) = patch.language {
+            self.settings.language = language;
+        }
+        if let Some(word_pack) = patch.word_pack {
+            self.settings.word_pack = word_pack;
+        }
+        if let Some(max) = patch.max_observers {
+            self.settings.max_observers = max;
+        }
+        if let Some(observer_chat) = patch.observer_chat {
+            self.settings.observer_chat = observer_chat;
+        }
+        if let Some(guess_along) = patch.guess_along {
+            self.settings.guess_along = guess_along;
+        }
+        if let Some(mid_game_join) = patch.mid_game_join {
+            self.settings.mid_game_join = mid_game_join;
+        }
+        if let Some(late_join_score) = patch.late_join_score {
+            self.settings.late_join_score = late_join_score;
+        }
+        if let Some(mode) = patch.mode {
+            self.settings.mode = mode;
+        }
+        if let Some(team_mode) = patch.team_mode {
+            self.settings.team_mode = team_mode;
+        }
+        if let Some(sec) = patch.steal_delay {
+            self.settings.steal_delay = sec;
+        }
+        if let Some(ratio) = patch.custom_word_ratio {
+            self.settings.custom_word_ratio = ratio;
+        }
+        Ok(())
+    }
+    /// 房主跳过全员准备直接开始
+    pub fn check_force_start(&self, user_id: &String) -> ApiResult<()> {
+        self.check_owner(user_id)?;
+        if self.stage != GameStage::Ready {
+            return Err(ApiError::WrongStage)
+        }
+        if self.seat.iter().filter(|seat| seat.is_some()).count() < MIN_FORCE_START_PEOPLE {
+            return Err(ApiError::NotEnoughPlayers)
+        }
+        Ok(())
     }
     /// 选词
-    pub fn pick_a_word(&mut self, user_id: &String, word: String) -> bool {
+    pub fn pick_a_word(&mut self, user_id: &String, word: String) -> ApiResult<()> {
         if word.len() == 0{
-            return false
+            return Err(ApiError::InvalidPayload)
+        }
+        if self.stage != GameStage::Choose {
+            return Err(ApiError::WrongStage)
         }
-        if self.stage == GameStage::Choose && self.is_current_player(user_id) {
+        if self.is_current_player(user_id) {
             self.word = word;
             self.stage = GameStage::Drawing;
-            self.next_timestamp = Some(next_time(DRAW_TIME as i64));
-            true
+            self.round_guesses.clear();
+            self.spectator_guesses.clear();
+            self.round_events.clear();
+            self.round_events.push(RoundEvent::now(RoundAction::Background { color: self.background.clone() }));
+            self.draw_started_at = Some(chrono::Local::now().timestamp_millis());
+            self.next_timestamp = Some(next_time(self.settings.draw_time as i64));
+            Ok(())
         } else {
-            false
+            Err(ApiError::NotYourTurn)
         }
     }
-    pub fn draw(&mut self, user_id: &String, data: String, timestamp: i64) -> bool {
-        match self.stage {
-            GameStage::Drawing => {
-                if self.is_current_player(user_id) {
-                    self.draw_data.draw(data, timestamp);
-                    return true
-                }
-            },
-            _ => ()
+    pub fn draw(&mut self, user_id: &String, data: String, timestamp: i64) -> ApiResult<()> {
+        self.draw_op(user_id, DrawOp::Path { data }, timestamp)
+    }
+    /// 添加绘制操作（直线、矩形、椭圆、填充、橡皮擦等）
+    pub fn draw_op(&mut self, user_id: &String, op: DrawOp, timestamp: i64) -> ApiResult<()> {
+        if self.stage == GameStage::FreeDraw {
+            return self.free_draw_op(user_id, op, timestamp)
         }
-        false
+        // 传话模式每人画自己的画布
+        if self.stage == GameStage::Telephone {
+            let canvas = self.telephone_canvas(user_id)?;
+            return if canvas.len() < MAX_DRAW_UNITS && canvas.draw_op(op, timestamp) {
+                Ok(())
+            } else {
+                Err(ApiError::InvalidPayload)
+            }
+        }
+        self.check_drawer(user_id)?;
+        if self.draw_data.len() >= MAX_DRAW_UNITS || !self.draw_data.draw_op(op.clone(), timestamp) {
+            return Err(ApiError::InvalidPayload)
+        }
+        self.round_events.push(RoundEvent::now(RoundAction::Draw { op }));
+        Ok(())
     }
-    pub fn clear_draw(&mut self, user_id: &String) -> bool {
-        match self.stage {
-            GameStage::Drawing => {
-                if self.is_current_player(user_id) {
-                    self.draw_data.clear();
-                    return true
-                }
-            },
-            _ => ()
+    pub fn clear_draw(&mut self, user_id: &String) -> ApiResult<()> {
+        if self.stage == GameStage::FreeDraw {
+            return self.free_draw_clear(user_id)
         }
-        false
+        if self.stage == GameStage::Telephone {
+            self.telephone_canvas(user_id)?.clear();
+            return Ok(())
+        }
+        self.check_drawer(user_id)?;
+        self.draw_data.clear();
+        self.round_events.push(RoundEvent::now(RoundAction::Clear));
+        Ok(())
     }
-    pub fn undo_draw(&mut self, user_id: &String) -> bool {
-        match self.stage {
-            GameStage::Drawing => {
-                if self.is_current_player(user_id) {
-                    self.draw_data.undo();
-                    return true
-                }
-            },
-            _ => ()
+    pub fn undo_draw(&mut self, user_id: &String) -> ApiResult<()> {
+        if self.stage == GameStage::FreeDraw {
+            return self.free_draw_undo(user_id)
         }
-        false
+        if self.stage == GameStage::Telephone {
+            self.telephone_canvas(user_id)?.undo();
+            return Ok(())
+        }
+        self.check_drawer(user_id)?;
+        self.draw_data.undo();
+        self.round_events.push(RoundEvent::now(RoundAction::Undo));
+        Ok(())
+    }
+    /// 绘画阶段且是当前回合的玩家才能操作画布
+    fn check_drawer(&self, user_id: &String) -> ApiResult<()> {
+        if self.stage != GameStage::Drawing {
+            return Err(ApiError::WrongStage)
+        }
+        if !self.is_current_player(user_id) {
+            return Err(ApiError::NotYourTurn)
+        }
+        Ok(())
     }
     pub fn is_current_player(&self, user_id: &String) -> bool{
         self.cur_id.as_deref() == Some(user_id.as_ref())
     }
-    /// 设置画布背景颜色
-    pub fn set_background(&mut self, user_id: &String, color: &String) {
-        if self.is_current_player(user_id) {
-            self.background = color.to_owned();
+    /// 设置画布背景颜色，协作画板中座位上的玩家都能设置
+    pub fn set_background(&mut self, user_id: &String, color: &String) -> ApiResult<()> {
+        if self.stage == GameStage::FreeDraw {
+            self.check_free_drawer(user_id)?;
+        } else if !self.is_current_player(user_id) {
+            return Err(ApiError::NotYourTurn)
+        }
+        if !is_valid_color(color) {
+            return Err(ApiError::InvalidPayload)
+        }
+        self.background = color.to_owned();
+        if self.stage == GameStage::Drawing {
+            self.round_events.push(RoundEvent::now(RoundAction::Background { color: color.to_owned() }));
         }
+        Ok(())
     }
 }
 
-fn next_time(sec: i64) -> i64 {
-    chrono::Local::now().timestamp_millis() + sec * 1000
+/// 房主可以修改的房间设置
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSettings {
+    /// 选词时间（秒）
+    pub choose_time: u8,
+    /// 绘制时间（秒）
+    pub draw_time: u8,
+    /// 房间语言，用于匹配
+    pub language: String,
+    /// 使用的词库
+    pub word_pack: String,
+    /// 最多观战人数
+    pub max_observers: u8,
+    /// 观战玩家能否发言
+    pub observer_chat: bool,
+    /// 观战玩家能否跟猜，猜对只记录不计分
+    pub guess_along: bool,
+    /// 游戏中加入房间的方式
+    pub mid_game_join: MidGameJoin,
+    /// 中途坐下的玩家的初始分
+    pub late_join_score: LateJoinScore,
+    /// 游戏模式
+    pub mode: GameMode,
+    /// 团队模式
+    pub team_mode: bool,
+    /// 团队模式下对方队伍开始抢答的时间（秒）
+    pub steal_delay: u8,
+    /// 候选词中自定义词的比例（百分比），没有上传自定义词库时不生效
+    pub custom_word_ratio: u8,
 }
 
-#[derive(Debug, Serialize)]
-pub struct RoomMeta {
-    pub id: String,
-    pub name: String,
+/// 需要修改的设置，`None` 表示不变
+#[derive(Debug, Default)]
+pub struct SettingsPatch {
+    pub name: Option<String>,
+    pub choose_time: Option<u8>,
+    pub draw_time: Option<u8>,
+    pub language: Option<String>,
+    pub word_pack: Option<String>,
+    pub max_observers: Option<u8>,
+    pub observer_chat: Option<bool>,
+    pub guess_along: Option<bool>,
+    pub mid_game_join: Option<MidGameJoin>,
+    pub late_join_score: Option<LateJoinScore>,
+    pub mode: Option<GameMode>,
+    pub team_mode: Option<bool>,
+    pub steal_delay: Option<u8>,
+    pub custom_word_ratio: Option<u8>,
 }
 
-impl RoomMeta {
-    pub fn from_room_data(target: &RoomData) -> Self {
-        Self {
-            id: target.id.clone(),
-            name: target.name.clone()
-        }
+/// 把聊天内容里的答案替换成 `*`，个数按字形计，与显示的字数一致
+pub(crate) fn mask_answer(content: &str, word: &str) -> String {
+    if word.is_empty() {
+        return content.to_string()
     }
+    content.replace(word, &"*".repeat(word.graphemes(true).count()))
 }
 
-impl WrapToValue for Option<&RoomData> {
-    fn to_value(&self) -> Value {
-        let a = self;
-        if let Some(room) = a {
-            serde_json::to_value(room).unwrap_or_default()
-        } else {
-            Default::default()
+/// 语言、词库等标识只允许字母数字与 `-` `_`
+pub fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && tag.len() <= 32 && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl RoomSettings {
+    pub const CHOOSE_TIME_RANGE: std::ops::RangeInclusive<u8> = 5..=60;
+    pub const DRAW_TIME_RANGE: std::ops::RangeInclusive<u8> = 10..=240;
+    pub const MAX_OBSERVERS_RANGE: std::ops::RangeInclusive<u8> = 0..=50;
+    pub const STEAL_DELAY_RANGE: std::ops::RangeInclusive<u8> = 0..=120;
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            choose_time: CHOOSE_TIME,
+            draw_time: DRAW_TIME,
+            language: DEFAULT_LANGUAGE.to_string(),
+            word_pack: DEFAULT_WORD_PACK.to_string(),
+            max_observers: MAX_OBSERVERS,
+            observer_chat: true,
+            guess_along: true,
+            mid_game_join: Default::default(),
+            late_join_score: Default::default(),
+            mode: Default::default(),
+            team_mode: false,
+            steal_delay: STEAL_DELAY,
+            custom_word_ratio: CUSTOM_WORD_RATIO,
         }
     }
 }
 
-impl WrapToValue for RoomData {
-    fn to_value(&self) -> Value {
-        serde_json::to_value(self).unwrap_or_default()
-    }
+/// 生成随机邀请码，由调用方保证不重复
+pub fn random_invite_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..INVITE_CODE_LEN)
+        .map(|_| INVITE_CODE_CHARSET[rng.gen_range(0..INVITE_CODE_CHARSET.len())] as char)
+        .collect()
 }
 
-impl PollingProvider for Option<&RoomData> {
-    fn snapshot(&self, user_id: &String, timestamp: i64, draw_index: usize) -> Value {
-        // serde_json::to_value(self).unwrap_or_default()
-        if let Some(room) = self {
-            let chat_history = if let Some(index) = room.chat_history.iter()
-                .position(|chat_item| chat_item.timestamp > timestamp) {
-                json!(room.chat_history[index..])
-            } else {
-                json!([])
-            };
+/// 创建房间的返回，私密房间附带邀请码
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomCreated {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<String>,
+}
 
-            let mut value = json!({
-                "seat": room.seat,
-                "observer": room.observer,
-                "stage": room.stage,
-                "curId": room.cur_id,
-                "nextTimestamp": room.next_timestamp,
-                "chatHistory": chat_history,
-                "drawLength": room.draw_data.len(),
-                "scoreMap": room.round_score_map,
-                "settlement": room.settlement,
-            });
+fn next_time(sec: i64) -> i64 {
+    chrono::Local::now().timestamp_millis() + sec * 1000
+}
 
-            if let Value::Object(ref mut map) = value {
-                match room.stage {
-                    GameStage::Ready => {
-                        map.insert("players".to_string(), json!(room.players));
-                        map.insert("readyState".to_string(), json!(room.ready_state));
-                    },
-                    GameStage::Drawing => {
-                        map.insert("drawList".to_string(), json!(room.draw_data.slice(draw_index)));
-                        map.insert("background".to_string(), json!(room.background));
-                        if Some(user_id.as_ref()) == room.cur_id.as_deref() {
-                            map.insert("word".to_string(), json!(room.word.as_bytes()));
-                        }
-                    },
-                    GameStage::Result => {
-                        map.insert("word".to_string(), json!(room.word.as_bytes()));
-                        map.insert("background".to_string(), json!(room.background));
-                    },
-                    _ => (),
-                }
-            }
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomMeta {
+    pub id: String,
+    pub name: String,
+    pub stage: GameStage,
+    /// 坐下的人数
+    pub seated: usize,
+    /// 观战人数
+    pub observers: usize,
+    /// 可用座位数，不含被锁定的座位
+    pub capacity: usize,
+    pub language: String,
+    pub word_pack: String,
+    pub private: bool,
+    pub owner: Option<String>,
+}
 
-            value
-        } else {
-            json!(null)
+impl RoomMeta {
+    pub fn from_room_data(target: &RoomData) -> Self {
+        Self {
+            id: target.id.clone(),
+            name: target.name.clone(),
+            stage: target.stage,
+            seated: target.seat.iter().flatten().count(),
+            observers: target.observer.len(),
+            capacity: target.locked_seats.iter().filter(|locked| !**locked).count(),
+            language: target.settings.language.clone(),
+            word_pack: target.settings.word_pack.clone(),
+            private: target.private,
+            owner: target.owner.clone(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ChatItem {
     pub content: String,
     #[serde(rename = "userId")]
@@ -494,13 +1002,35 @@ pub struct ChatItem {
 }
 
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum GameStage {
     Ready,
     Choose,
     Drawing,
     Result,
+    /// 传话模式进行中，具体阶段见 [`TelephoneGame`]
+    Telephone,
+    /// 协作画板进行中
+    FreeDraw,
+}
+
+/// 游戏模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum GameMode {
+    /// 轮流画图，其他人猜词
+    Classic,
+    /// 传话：写题目、画图、看图描述依次传给下一个人
+    Telephone,
+    /// 协作画板：座位上的玩家一起画，不出词也不计分
+    FreeDraw,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Classic
+    }
 }
 
 /// 1. 座位上的玩家
@@ -511,6 +1041,22 @@ pub enum IfAllReadyResult {
     AutoStart(u8),
 }
 
+#[cfg(test)]
+impl RoomData {
+    /// 测试用的房间，玩家按顺序加入
+    pub fn with_players<I, T>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: ToString,
+    {
+        let mut room = RoomData::new();
+        for id in ids {
+            room.join_player(&Player::test(&id.to_string()));
+        }
+        room
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::player::Player;
@@ -519,23 +1065,7 @@ mod test {
 
     #[test]
     fn room_next() {
-        let mut room = RoomData::new();
-        
-        room.join_player(&Player {
-            avatar_url: "1".to_string(),
-            user_id: "1".to_string(),
-            nick_name: "1".to_string(),
-        });
-        room.join_player(&Player {
-            avatar_url: "2".to_string(),
-            user_id: "2".to_string(),
-            nick_name: "2".to_string(),
-        });
-        room.join_player(&Player {
-            avatar_url: "3".to_string(),
-            user_id: "3".to_string(),
-            nick_name: "3".to_string(),
-        });
+        let mut room = RoomData::with_players(&["1", "2", "3"]);
         room.start();
         assert_eq!(room.cur_id, Some("1".to_string()));
         assert_eq!(room.cur_seat, Some(0));
@@ -551,4 +1081,134 @@ mod test {
         println!("{:?}", room.chat_history);
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn room_mask_answer() {
+        use super::mask_answer;
+
+        assert_eq!(mask_answer("大狮子", "大狮子"), "***");
+        assert_eq!(mask_answer("是大狮子吗", "大狮子"), "是***吗");
+        // 组合字符与 emoji 各算一个字
+        assert_eq!(mask_answer("cafe\u{301}!", "cafe\u{301}"), "****!");
+        assert_eq!(mask_answer("👨‍👩‍👧猫", "👨‍👩‍👧"), "*猫");
+        assert_eq!(mask_answer("猫", ""), "猫");
+    }
+
+    #[test]
+    fn room_private_access() {
+        use crate::error::ApiError;
+
+        let code = super::random_invite_code();
+        assert_eq!(code.len(), super::INVITE_CODE_LEN);
+        assert!(!code.contains('0') && !code.contains('O'));
+
+        assert!(RoomData::new().check_access(None, None).is_ok());
+
+        let room = RoomData::new_private(Some("secret".to_string()), code.clone());
+        assert!(room.private);
+        assert_eq!(room.check_access(None, None), Err(ApiError::WrongPassword));
+        assert_eq!(room.check_access(Some("wrong"), None), Err(ApiError::WrongPassword));
+        assert!(room.check_access(Some("secret"), None).is_ok());
+        assert!(room.check_access(None, Some(&code.to_lowercase())).is_ok());
+
+        let room = RoomData::new_private(None, code.clone());
+        assert_eq!(room.check_access(Some(""), None), Err(ApiError::WrongPassword));
+        assert!(room.check_access(None, Some(&code)).is_ok());
+    }
+
+    #[test]
+    fn room_owner() {
+        use crate::error::ApiError;
+        use super::SettingsPatch;
+
+        let mut room = RoomData::with_players(&["1", "2", "3"]);
+        let (one, two, three) = ("1".to_string(), "2".to_string(), "3".to_string());
+        assert_eq!(room.owner.as_deref(), Some("1"));
+        assert_eq!(room.kick(&two, &three), Err(ApiError::NotOwner));
+
+        // 锁定的座位不能坐
+        assert_eq!(room.lock_seat(&one, 0, true), Err(ApiError::SeatTaken));
+        room.lock_seat(&one, 3, true).unwrap();
+        room.move_to_observer(&three).unwrap();
+        assert_eq!(room.sit_on(&three, 3), Err(ApiError::SeatLocked));
+        room.sit_on(&three, 2).unwrap();
+
+        room.update_settings(&one, SettingsPatch {
+            name: Some("新房间".to_string()),
+            draw_time: Some(60),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(room.name, "新房间");
+        assert_eq!(room.settings.draw_time, 60);
+        assert_eq!(room.update_settings(&one, SettingsPatch { choose_time: Some(1), ..Default::default() }), Err(ApiError::InvalidPayload));
+        assert_eq!(room.update_settings(&one, SettingsPatch { language: Some("z h".to_string()), ..Default::default() }), Err(ApiError::InvalidPayload));
+
+        room.ban(&one, &three).unwrap();
+        assert!(!room.players.contains_key("3"));
+        assert_eq!(room.check_banned(&three), Err(ApiError::Banned));
+
+        // 房主离开后由最早进入且有座位的玩家接任
+        room.join_player(&Player::test("4"));
+        let four = "4".to_string();
+        room.move_to_observer(&two).unwrap();
+        room.player_leave(&one);
+        assert_eq!(room.owner.as_deref(), Some("4"));
+        room.transfer_owner(&four, &two).unwrap();
+        assert_eq!(room.check_force_start(&two), Err(ApiError::NotEnoughPlayers));
+        room.transfer_owner(&two, &two).unwrap();
+        room.stage = super::GameStage::Drawing;
+        assert_eq!(room.update_settings(&two, SettingsPatch::default()), Err(ApiError::WrongStage));
+    }
+
+    /// 踢出正在画图的玩家后回合继续往下走
+    #[test]
+    fn room_kick_drawer() {
+        use super::GameStage;
+
+        let mut room = RoomData::with_players(&["1", "2", "3"]);
+        let (one, two, three) = ("1".to_string(), "2".to_string(), "3".to_string());
+        room.start();
+        room.next_player();
+        assert_eq!(room.cur_id.as_ref(), Some(&two));
+        // 选词阶段直接轮到下一位
+        assert_eq!(room.kick(&one, &two), Ok(Some(GameStage::Choose)));
+        assert_eq!(room.cur_id.as_ref(), Some(&three));
+        assert_eq!(room.stage, GameStage::Choose);
+        // 绘画阶段进入结算
+        room.pick_a_word(&three, "苹果".to_string()).unwrap();
+        assert_eq!(room.ban(&one, &three), Ok(Some(GameStage::Drawing)));
+        assert_eq!(room.stage, GameStage::Result);
+        assert_eq!(room.gallery.len(), 1);
+        // 其他玩家离开不影响回合
+        assert_eq!(room.player_leave(&one), None);
+    }
+
+    /// 回合结束后存入画廊，包括词汇、猜对的玩家与缩略图
+    #[test]
+    fn room_archive_round() {
+        use crate::{draw_data::{DrawOp, Point}, render::{CanvasFormat, THUMBNAIL_SIZE}};
+
+        let mut room = RoomData::with_players(&["1", "2", "3"]);
+        let one = "1".to_string();
+        room.start();
+        room.pick_a_word(&one, "苹果".to_string()).unwrap();
+        room.draw(&one, "4;#ff0000;1|\u{a}\u{14}\u{1e}\u{28}".to_string(), 1).unwrap();
+        room.draw_op(&one, DrawOp::Fill { at: Point { x: 250, y: 250 }, color: "#00ff00".to_string() }, 2).unwrap();
+        room.add_chat("3".to_string(), "苹果".to_string());
+        room.add_chat("2".to_string(), "香蕉".to_string());
+        room.draw_end();
+
+        assert_eq!(room.gallery.len(), 1);
+        let record = &room.gallery[0];
+        assert_eq!(record.round, 0);
+        assert_eq!(record.word, "苹果");
+        assert_eq!(record.drawer_id, "1");
+        assert_eq!(record.draw_list.len(), 2);
+        assert_eq!(record.guessers.len(), 1);
+        assert_eq!(record.guessers[0].user_id, "3");
+        assert_eq!(record.guessers[0].order, 1);
+        assert!(record.scores.contains_key("3"));
+        let thumbnail = record.canvas().encode(CanvasFormat::Png, THUMBNAIL_SIZE).unwrap();
+        assert!(thumbnail.starts_with(b"\x89PNG"));
+    }
+}
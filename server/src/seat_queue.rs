@@ -0,0 +1,105 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{error::{ApiError, ApiResult}, room::{GameStage, RoomData}};
+
+/// 空出的座位留给排队玩家的时间（秒）
+pub const SEAT_CLAIM_WINDOW: u8 = 10;
+
+/// 留给排队玩家的座位
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatOffer {
+    /// 序号，用于匹配过期定时器
+    pub seq: u32,
+    pub user_id: String,
+    pub pos: usize,
+    pub expires_at: i64,
+}
+
+impl RoomData {
+    /// 观战玩家排队等座位，返回从 1 开始的位置
+    pub fn enqueue_seat(&mut self, user_id: &String) -> ApiResult<usize> {
+        if !self.observer.contains(user_id) {
+            return Err(ApiError::NotObserver)
+        }
+        if !self.seat_queue.contains(user_id) {
+            self.seat_queue.push(user_id.clone());
+        }
+        Ok(self.seat_queue.iter().position(|id| id == user_id).map_or(0, |index| index + 1))
+    }
+
+    /// 退出排队，放弃留给自己的座位
+    pub fn dequeue_seat(&mut self, user_id: &String) {
+        self.seat_queue.retain(|id| id != user_id);
+        if self.seat_offer.as_ref().map_or(false, |offer| &offer.user_id == user_id) {
+            self.seat_offer = None;
+        }
+    }
+
+    /// 正在留给排队玩家的座位
+    pub fn reserved_seat(&self) -> Option<usize> {
+        self.seat_offer.as_ref().map(|offer| offer.pos)
+    }
+
+    /// 准备阶段有空座位时按排队顺序留给下一位，返回新留座的序号
+    pub fn offer_seat(&mut self, now: i64) -> Option<u32> {
+        if self.stage != GameStage::Ready || self.seat_offer.is_some() {
+            return None
+        }
+        // 排队的人可能已经离开或坐下了
+        let observer = &self.observer;
+        self.seat_queue.retain(|id| observer.contains(id));
+        if self.seat_queue.is_empty() {
+            return None
+        }
+        let pos = self.free_seat()?;
+        let user_id = self.seat_queue.remove(0);
+        self.seat_offer_seq += 1;
+        let nick_name = self.players.get(&user_id).map_or_else(|| user_id.clone(), |player| player.nick_name.clone());
+        self.add_system_chat(format!("{}号座位空出，请{}在{}秒内入座", pos + 1, nick_name, SEAT_CLAIM_WINDOW));
+        self.seat_offer = Some(SeatOffer {
+            seq: self.seat_offer_seq,
+            user_id,
+            pos,
+            expires_at: now + SEAT_CLAIM_WINDOW as i64 * 1000,
+        });
+        Some(self.seat_offer_seq)
+    }
+
+    /// 留座到期，`seq` 不一致说明已经入座或放弃
+    pub fn expire_seat_offer(&mut self, seq: u32) -> bool {
+        match self.seat_offer.take() {
+            Some(offer) if offer.seq == seq => true,
+            offer => {
+                self.seat_offer = offer;
+                false
+            },
+        }
+    }
+}
+
+#[test]
+fn test_seat_queue() {
+    let mut room = RoomData::with_players(0..8);
+    let id = |id: &str| id.to_string();
+    assert_eq!(room.enqueue_seat(&id("0")), Err(ApiError::NotObserver));
+    assert_eq!(room.enqueue_seat(&id("7")), Ok(1));
+    assert_eq!(room.enqueue_seat(&id("6")), Ok(2));
+    assert_eq!(room.enqueue_seat(&id("7")), Ok(1));
+    assert_eq!(room.offer_seat(0), None);
+
+    room.move_to_observer(&id("2")).unwrap();
+    assert_eq!(room.offer_seat(0), Some(1));
+    assert_eq!(room.reserved_seat(), Some(2));
+    assert!(!room.can_join());
+    assert_eq!(room.sit_on(&id("6"), 2), Err(ApiError::SeatTaken));
+
+    // 没有及时入座，留给下一位
+    assert!(!room.expire_seat_offer(2));
+    assert!(room.expire_seat_offer(1));
+    assert_eq!(room.offer_seat(0), Some(2));
+    room.sit_on(&id("6"), 2).unwrap();
+    assert!(room.seat_offer.is_none());
+    assert!(room.seat_queue.is_empty());
+}
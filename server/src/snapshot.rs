@@ -0,0 +1,491 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{
+    draw_data::{DrawData, DrawDataUnit},
+    player::Player,
+    seat_queue::SeatOffer,
+    spectator::SpectatorGuess,
+    team::Team,
+    free_draw::FreeDrawSnapshot,
+    telephone::TelephoneSnapshot,
+    vote::Vote,
+    room::{ChatItem, GameStage, RoomData, RoomMeta, RoomSettings, MAX_PEOPLE},
+};
+
+/// 轮训与房间信息的数据格式版本，字段有不兼容的变动时递增
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// 管理员口令，由环境变量 `ADMIN_TOKEN` 配置，未配置时没有管理员
+static ADMIN_TOKEN: Lazy<Option<String>> = Lazy::new(|| {
+    std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty())
+});
+
+pub fn is_admin(token: Option<&str>) -> bool {
+    matches!((ADMIN_TOKEN.as_deref(), token), (Some(expected), Some(token)) if expected == token)
+}
+
+/// 查看房间的身份，决定能看到哪些数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewer {
+    /// 不在房间内
+    Public,
+    /// 座位上的玩家
+    Player,
+    /// 观战的玩家
+    Observer,
+    /// 当前回合画图的玩家
+    Drawer,
+    /// 管理员，能看到全部数据
+    Admin,
+}
+
+impl Viewer {
+    /// 能否看到答案，回合结束后所有人都能看到
+    pub fn can_see_word(self, stage: GameStage) -> bool {
+        match self {
+            Viewer::Admin => true,
+            Viewer::Drawer => stage != GameStage::Ready,
+            Viewer::Player | Viewer::Observer | Viewer::Public => stage == GameStage::Result,
+        }
+    }
+
+    /// 观战频道在绘制阶段只对观战玩家可见，避免泄露答案
+    pub fn can_see_spectator_chat(self, stage: GameStage) -> bool {
+        match self {
+            Viewer::Admin | Viewer::Observer => true,
+            Viewer::Player | Viewer::Drawer => stage != GameStage::Drawing,
+            Viewer::Public => false,
+        }
+    }
+
+    /// 聊天记录只对房间内的玩家可见
+    pub fn can_see_chat(self) -> bool {
+        self != Viewer::Public
+    }
+}
+
+/// 轮训返回的房间快照
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PollSnapshot {
+    pub version: u32,
+    pub owner: Option<String>,
+    pub seat: [Option<String>; MAX_PEOPLE],
+    pub locked_seats: [bool; MAX_PEOPLE],
+    pub observer: HashSet<String>,
+    /// 排队等座位的玩家，按先后排列
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub seat_queue: Vec<String>,
+    /// 正在留给排队玩家的座位
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seat_offer: Option<SeatOffer>,
+    pub stage: GameStage,
+    pub cur_id: Option<String>,
+    pub next_timestamp: Option<i64>,
+    /// 客户端时间戳之后的聊天记录
+    pub chat_history: Vec<ChatItem>,
+    /// 客户端时间戳之后的观战频道聊天
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spectator_chat: Vec<ChatItem>,
+    /// 观战玩家的跟猜记录
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spectator_guesses: Vec<SpectatorGuess>,
+    pub draw_length: usize,
+    pub score_map: HashMap<String, u16>,
+    pub settlement: HashMap<String, u16>,
+    /// 团队模式下玩家的队伍
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub teams: HashMap<String, Team>,
+    /// 团队模式下各队总分
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub team_settlement: HashMap<Team, u16>,
+    /// 正在进行的投票
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vote: Option<Vote>,
+    /// 随阶段变化的数据
+    #[serde(flatten)]
+    pub detail: StageSnapshot,
+}
+
+/// 各阶段特有的数据
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum StageSnapshot {
+    Ready(ReadySnapshot),
+    Choose(ChooseSnapshot),
+    Drawing(DrawingSnapshot),
+    Result(ResultSnapshot),
+    Telephone(TelephoneSnapshot),
+    FreeDraw(FreeDrawSnapshot),
+}
+
+/// 准备阶段：玩家信息、准备状态与房间设置
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadySnapshot {
+    pub players: HashMap<String, Player>,
+    pub ready_state: HashMap<String, bool>,
+    pub settings: RoomSettings,
+}
+
+/// 选词阶段没有额外数据
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ChooseSnapshot {}
+
+/// 绘制阶段：新增的笔画，画图的玩家能拿到词汇
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DrawingSnapshot {
+    pub draw_list: Vec<DrawDataUnit>,
+    pub background: String,
+    /// 词汇的 utf-8 字节，只有画图的玩家才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word: Option<Vec<u8>>,
+}
+
+/// 回合结束：公布词汇
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultSnapshot {
+    /// 词汇的 utf-8 字节
+    pub word: Vec<u8>,
+    pub background: String,
+}
+
+/// 按身份裁剪后的房间信息
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomView {
+    pub version: u32,
+    pub name: String,
+    pub owner: Option<String>,
+    pub settings: RoomSettings,
+    pub players: HashMap<String, Player>,
+    pub seat: [Option<String>; MAX_PEOPLE],
+    pub locked_seats: [bool; MAX_PEOPLE],
+    pub ready_state: HashMap<String, bool>,
+    pub observer: HashSet<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub seat_queue: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seat_offer: Option<SeatOffer>,
+    /// 不在房间内时没有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_history: Option<Vec<ChatItem>>,
+    /// 绘制阶段只有观战玩家有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spectator_chat: Option<Vec<ChatItem>>,
+    pub stage: GameStage,
+    pub cur_id: Option<String>,
+    pub next_timestamp: Option<i64>,
+    /// 只有画图的玩家、管理员，或回合结束后才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word: Option<String>,
+    pub draw_data: DrawData,
+    pub background: String,
+    pub round_score_map: HashMap<String, u16>,
+    pub settlement: HashMap<String, u16>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub teams: HashMap<String, Team>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub team_settlement: HashMap<Team, u16>,
+    /// 传话模式进行中才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telephone: Option<TelephoneSnapshot>,
+}
+
+/// `/init` 的返回
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct InitSnapshot {
+    pub room: RoomView,
+    pub user: Player,
+    pub meta: RoomMeta,
+}
+
+impl RoomData {
+    /// 玩家在本房间的身份，`None` 表示匿名访问
+    pub fn viewer(&self, user_id: Option<&str>) -> Viewer {
+        match user_id {
+            Some(id) if self.cur_id.as_deref() == Some(id) => Viewer::Drawer,
+            Some(id) if self.observer.contains(id) => Viewer::Observer,
+            Some(id) if self.players.contains_key(id) => Viewer::Player,
+            _ => Viewer::Public,
+        }
+    }
+
+    /// 生成轮训快照，`timestamp` 之前的聊天与 `draw_index` 之前的笔画客户端已经有了
+    pub fn snapshot(&self, viewer: Viewer, timestamp: i64, draw_index: usize) -> PollSnapshot {
+        let chat_history = self.chat_history.iter()
+            .position(|chat_item| chat_item.timestamp > timestamp)
+            .filter(|_| viewer.can_see_chat())
+            .map(|index| self.chat_history[index..].to_vec())
+            .unwrap_or_default();
+        let see_spectator = viewer.can_see_spectator_chat(self.stage);
+        let spectator_chat = self.spectator_chat.iter()
+            .filter(|chat_item| see_spectator && chat_item.timestamp > timestamp)
+            .cloned()
+            .collect();
+        let spectator_guesses = if see_spectator {
+            self.spectator_guesses.clone()
+        } else {
+            vec![]
+        };
+        let detail = match self.stage {
+            GameStage::Ready => StageSnapshot::Ready(ReadySnapshot {
+                players: self.players.clone(),
+                ready_state: self.ready_state.clone(),
+                settings: self.settings.clone(),
+            }),
+            GameStage::Choose => StageSnapshot::Choose(ChooseSnapshot {}),
+            GameStage::Drawing => StageSnapshot::Drawing(DrawingSnapshot {
+                draw_list: self.draw_data.slice(draw_index).to_vec(),
+                background: self.background.clone(),
+                word: if viewer.can_see_word(self.stage) {
+                    Some(self.word.as_bytes().to_vec())
+                } else {
+                    None
+                },
+            }),
+            GameStage::Result => StageSnapshot::Result(ResultSnapshot {
+                word: self.word.as_bytes().to_vec(),
+                background: self.background.clone(),
+            }),
+            GameStage::Telephone => match self.telephone_snapshot() {
+                Some(telephone) => StageSnapshot::Telephone(telephone),
+                None => StageSnapshot::Choose(ChooseSnapshot {}),
+            },
+            GameStage::FreeDraw => StageSnapshot::FreeDraw(self.free_draw_snapshot(draw_index)),
+        };
+        PollSnapshot {
+            version: SNAPSHOT_VERSION,
+            owner: self.owner.clone(),
+            seat: self.seat.clone(),
+            locked_seats: self.locked_seats,
+            observer: self.observer.clone(),
+            seat_queue: self.seat_queue.clone(),
+            seat_offer: self.seat_offer.clone(),
+            stage: self.stage,
+            cur_id: self.cur_id.clone(),
+            next_timestamp: self.next_timestamp,
+            chat_history,
+            spectator_chat,
+            spectator_guesses,
+            draw_length: self.draw_data.len(),
+            score_map: self.round_score_map.clone(),
+            settlement: self.settlement.clone(),
+            teams: self.teams.clone(),
+            team_settlement: self.team_settlement(),
+            vote: self.vote.clone(),
+            detail,
+        }
+    }
+
+    pub fn view(&self, viewer: Viewer) -> RoomView {
+        RoomView {
+            version: SNAPSHOT_VERSION,
+            name: self.name.clone(),
+            owner: self.owner.clone(),
+            settings: self.settings.clone(),
+            players: self.players.clone(),
+            seat: self.seat.clone(),
+            locked_seats: self.locked_seats,
+            ready_state: self.ready_state.clone(),
+            observer: self.observer.clone(),
+            seat_queue: self.seat_queue.clone(),
+            seat_offer: self.seat_offer.clone(),
+            chat_history: if viewer.can_see_chat() {
+                Some(self.chat_history.clone())
+            } else {
+                None
+            },
+            spectator_chat: if viewer.can_see_spectator_chat(self.stage) {
+                Some(self.spectator_chat.clone())
+            } else {
+                None
+            },
+            stage: self.stage,
+            cur_id: self.cur_id.clone(),
+            next_timestamp: self.next_timestamp,
+            word: if viewer.can_see_word(self.stage) {
+                Some(self.word.clone())
+            } else {
+                None
+            },
+            draw_data: self.draw_data.clone(),
+            background: self.background.clone(),
+            round_score_map: self.round_score_map.clone(),
+            settlement: self.settlement.clone(),
+            teams: self.teams.clone(),
+            team_settlement: self.team_settlement(),
+            telephone: self.telephone_snapshot(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use super::Viewer;
+    use crate::{draw_data::DrawOp, player::Player, room::{GameStage, RoomData}};
+
+    /// 1 号画图，2 号猜词，3 号观战
+    fn room(stage: GameStage) -> RoomData {
+        let mut room = RoomData::with_players(&["1", "2"]);
+        room.players.insert("3".to_string(), Player::test("3"));
+        room.observer.insert("3".to_string());
+        room.stage = stage;
+        room.cur_id = Some("1".to_string());
+        room.next_timestamp = Some(1000);
+        room.word = "苹果".to_string();
+        room.draw_data.draw_op(DrawOp::Path { data: "2;#000000;1|".to_string() }, 10);
+        room
+    }
+
+    fn base(stage: &str) -> Value {
+        json!({
+            "version": 1,
+            "owner": "1",
+            "seat": ["1", "2", null, null, null, null],
+            "lockedSeats": [false, false, false, false, false, false],
+            "observer": ["3"],
+            "stage": stage,
+            "curId": "1",
+            "nextTimestamp": 1000,
+            "chatHistory": [],
+            "drawLength": 1,
+            "scoreMap": {},
+            "settlement": {},
+        })
+    }
+
+    fn with(mut value: Value, extra: Value) -> Value {
+        if let (Value::Object(map), Value::Object(extra)) = (&mut value, extra) {
+            map.extend(extra);
+        }
+        value
+    }
+
+    fn snapshot(stage: GameStage, user_id: &str) -> Value {
+        let room = room(stage);
+        serde_json::to_value(room.snapshot(room.viewer(Some(user_id)), 0, 0)).unwrap()
+    }
+
+    #[test]
+    fn test_ready_snapshot() {
+        for user_id in ["1", "2", "3"].iter() {
+            let value = snapshot(GameStage::Ready, user_id);
+            let players = value["players"].as_object().unwrap();
+            assert_eq!(players.len(), 3);
+            assert_eq!(value["readyState"], json!({}));
+            assert!(value.get("word").is_none());
+            assert!(value.get("drawList").is_none());
+        }
+    }
+
+    #[test]
+    fn test_choose_snapshot() {
+        for user_id in ["1", "2", "3"].iter() {
+            assert_eq!(snapshot(GameStage::Choose, user_id), base("choose"));
+        }
+    }
+
+    #[test]
+    fn test_drawing_snapshot() {
+        let draw_list = json!([{ "kind": "path", "data": "2;#000000;1|", "timestamp": 10 }]);
+        let guesser = with(base("drawing"), json!({
+            "drawList": draw_list,
+            "background": "#ffffff",
+        }));
+        let drawer = with(guesser.clone(), json!({ "word": "苹果".as_bytes() }));
+        assert_eq!(snapshot(GameStage::Drawing, "1"), drawer);
+        assert_eq!(snapshot(GameStage::Drawing, "2"), guesser);
+        assert_eq!(snapshot(GameStage::Drawing, "3"), guesser);
+    }
+
+    #[test]
+    fn test_result_snapshot() {
+        let expected = with(base("result"), json!({
+            "word": "苹果".as_bytes(),
+            "background": "#ffffff",
+        }));
+        for user_id in ["1", "2", "3"].iter() {
+            assert_eq!(snapshot(GameStage::Result, user_id), expected);
+        }
+    }
+
+    #[test]
+    fn test_incremental_snapshot() {
+        let mut room = room(GameStage::Drawing);
+        room.chat_history.push(crate::room::ChatItem {
+            content: "你好".to_string(),
+            user_id: "2".to_string(),
+            timestamp: 500,
+        });
+        let value = serde_json::to_value(room.snapshot(Viewer::Player, 500, 1)).unwrap();
+        assert_eq!(value["chatHistory"], json!([]));
+        assert_eq!(value["drawList"], json!([]));
+        assert_eq!(value["drawLength"], 1);
+    }
+
+    const STAGES: [GameStage; 4] = [GameStage::Ready, GameStage::Choose, GameStage::Drawing, GameStage::Result];
+
+    #[test]
+    fn test_viewer() {
+        let room = room(GameStage::Drawing);
+        assert_eq!(room.viewer(Some("1")), Viewer::Drawer);
+        assert_eq!(room.viewer(Some("2")), Viewer::Player);
+        assert_eq!(room.viewer(Some("3")), Viewer::Observer);
+        assert_eq!(room.viewer(Some("4")), Viewer::Public);
+        assert_eq!(room.viewer(None), Viewer::Public);
+    }
+
+    /// 回合结束前答案只对画图的玩家和管理员可见
+    #[test]
+    fn test_word_hidden_before_result() {
+        for &stage in STAGES.iter() {
+            let room = room(stage);
+            let revealed = stage == GameStage::Result;
+            for viewer in [Viewer::Public, Viewer::Player].iter() {
+                let view = serde_json::to_value(room.view(*viewer)).unwrap();
+                let snapshot = serde_json::to_value(room.snapshot(*viewer, 0, 0)).unwrap();
+                assert_eq!(view.get("word").is_some(), revealed, "{:?} {:?}", stage, viewer);
+                assert_eq!(snapshot.get("word").is_some(), revealed, "{:?} {:?}", stage, viewer);
+                assert!(!view.to_string().contains("苹果") || revealed);
+            }
+            let admin = serde_json::to_value(room.view(Viewer::Admin)).unwrap();
+            assert_eq!(admin["word"], "苹果");
+        }
+        let room = room(GameStage::Drawing);
+        assert_eq!(room.view(Viewer::Drawer).word.as_deref(), Some("苹果"));
+    }
+
+    #[test]
+    fn test_public_view_hides_chat() {
+        let mut room = room(GameStage::Drawing);
+        room.add_chat("2".to_string(), "你好".to_string());
+        assert!(room.view(Viewer::Public).chat_history.is_none());
+        assert!(room.snapshot(Viewer::Public, 0, 0).chat_history.is_empty());
+        assert_eq!(room.view(Viewer::Player).chat_history.map(|chat| chat.len()), Some(1));
+    }
+
+    /// 观战频道在绘制阶段对座位上的玩家隐藏
+    #[test]
+    fn test_spectator_chat_hidden_while_drawing() {
+        for &stage in STAGES.iter() {
+            let mut room = room(stage);
+            room.add_spectator_chat("3".to_string(), "好难".to_string()).unwrap();
+            let hidden = stage == GameStage::Drawing;
+            for viewer in [Viewer::Player, Viewer::Drawer].iter() {
+                assert_eq!(room.snapshot(*viewer, 0, 0).spectator_chat.is_empty(), hidden);
+                assert_eq!(room.view(*viewer).spectator_chat.is_none(), hidden);
+            }
+            assert_eq!(room.snapshot(Viewer::Observer, 0, 0).spectator_chat.len(), 1);
+            assert!(room.view(Viewer::Public).spectator_chat.is_none());
+        }
+    }
+}
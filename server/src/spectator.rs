@@ -0,0 +1,101 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{error::{ApiError, ApiResult}, late_join::MidGameJoin, room::{mask_answer, ChatItem, GameStage, RoomData, MAX_CHAT_HISTORY}};
+
+/// 观战玩家的跟猜记录
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectatorGuess {
+    pub user_id: String,
+    pub nick_name: String,
+    /// 第几个猜对，只在观战玩家之间排序
+    pub order: usize,
+    pub timestamp: i64,
+}
+
+impl RoomData {
+    /// 观战列表是否还有空位
+    pub fn can_observe(&self) -> bool {
+        self.observer.len() < self.settings.max_observers as usize
+    }
+
+    /// 新玩家没有座位时只能观战，观战也满了则不能加入
+    pub fn check_capacity(&self, user_id: &String) -> ApiResult<()> {
+        if self.players.contains_key(user_id) {
+            return Ok(())
+        }
+        if self.mid_game_join() == Some(MidGameJoin::Closed) {
+            return Err(ApiError::WrongStage)
+        }
+        if self.join_seat().is_some() || self.can_observe() {
+            Ok(())
+        } else {
+            Err(ApiError::ObserversFull)
+        }
+    }
+
+    /// 观战玩家发言，进入观战频道，绘制阶段开启跟猜时记录猜对的玩家
+    pub fn add_spectator_chat(&mut self, user_id: String, content: String) -> ApiResult<()> {
+        if !self.settings.observer_chat {
+            return Err(ApiError::ObserverChatDisabled)
+        }
+        let mut content = content;
+        let timestamp = chrono::Local::now().timestamp_millis();
+        let mut correct = None;
+        if self.stage == GameStage::Drawing && !self.word.is_empty() {
+            if content == self.word {
+                content = mask_answer(&content, &self.word);
+                let guessed = self.spectator_guesses.iter().any(|guess| guess.user_id == user_id);
+                if self.settings.guess_along && !guessed {
+                    let nick_name = self.players.get(&user_id).map(|p| p.nick_name.clone()).unwrap_or_default();
+                    correct = Some(format!("{}跟猜正确", nick_name));
+                    self.spectator_guesses.push(SpectatorGuess {
+                        user_id: user_id.clone(),
+                        nick_name,
+                        order: self.spectator_guesses.len() + 1,
+                        timestamp,
+                    });
+                }
+            } else {
+                content = mask_answer(&content, &self.word);
+            }
+        }
+        self.spectator_chat.push(ChatItem { content, user_id, timestamp });
+        if let Some(content) = correct {
+            self.spectator_chat.push(ChatItem { content, user_id: "0".to_string(), timestamp });
+        }
+        if self.spectator_chat.len() > MAX_CHAT_HISTORY {
+            self.spectator_chat.drain(..self.spectator_chat.len() - MAX_CHAT_HISTORY);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_spectator() {
+    use crate::room::SettingsPatch;
+
+    let mut room = RoomData::with_players(0..7);
+    let id = |id: &str| id.to_string();
+    assert!(room.observer.contains("6"));
+    room.update_settings(&id("0"), SettingsPatch { max_observers: Some(1), ..Default::default() }).unwrap();
+    assert_eq!(room.check_capacity(&id("7")), Err(ApiError::ObserversFull));
+    assert_eq!(room.check_capacity(&id("6")), Ok(()));
+    room.move_to_observer(&id("5")).unwrap();
+    assert_eq!(room.move_to_observer(&id("6")), Err(ApiError::NotSeated));
+
+    room.stage = GameStage::Drawing;
+    room.cur_id = Some(id("0"));
+    room.word = "苹果".to_string();
+    room.add_spectator_chat(id("6"), "苹果".to_string()).unwrap();
+    room.add_spectator_chat(id("6"), "苹果".to_string()).unwrap();
+    assert_eq!(room.spectator_guesses.len(), 1);
+    assert!(room.round_score_map.is_empty());
+    assert!(room.chat_history.is_empty());
+    assert!(room.spectator_chat.iter().all(|item| !item.content.contains("苹果")));
+
+    room.stage = GameStage::Ready;
+    room.update_settings(&id("0"), SettingsPatch { observer_chat: Some(false), ..Default::default() }).unwrap();
+    assert_eq!(room.add_spectator_chat(id("6"), "你好".to_string()), Err(ApiError::ObserverChatDisabled));
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{ApiError, ApiResult}, room::{GameStage, RoomData}};
+
+/// 对方队伍抢答得分
+pub const STEAL_SCORE: u16 = 2;
+/// 每个答对的队友给画图玩家加的分
+pub const TEAMMATE_BONUS: u16 = 2;
+/// 默认开始抢答的时间（秒）
+pub const STEAL_DELAY: u8 = 20;
+
+/// 队伍
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Red, Team::Blue];
+}
+
+/// 猜词的身份
+#[derive(Debug, PartialEq, Eq)]
+pub enum GuessKind {
+    /// 画图玩家的队友，或非团队模式
+    Normal,
+    /// 对方队伍抢答
+    Steal,
+    /// 对方队伍还不能抢答
+    TooEarly,
+}
+
+impl RoomData {
+    pub fn team_of(&self, user_id: &String) -> Option<Team> {
+        self.teams.get(user_id).copied()
+    }
+
+    /// 准备阶段坐下的玩家选择队伍
+    pub fn choose_team(&mut self, user_id: &String, team: Team) -> ApiResult<()> {
+        if !self.settings.team_mode || self.stage != GameStage::Ready {
+            return Err(ApiError::WrongStage)
+        }
+        if !self.seat.iter().flatten().any(|id| id == user_id) {
+            return Err(ApiError::NotSeated)
+        }
+        self.teams.insert(user_id.clone(), team);
+        Ok(())
+    }
+
+    /// 开局时分配队伍：没选的进人少的队，一队没人时从另一队调人，然后排出交替的画图顺序
+    pub(crate) fn assign_teams(&mut self) {
+        let seated = self.seat.iter().flatten().cloned().collect::<Vec<_>>();
+        self.teams.retain(|id, _| seated.contains(id));
+        for id in seated.iter() {
+            if !self.teams.contains_key(id) {
+                let team = self.smaller_team();
+                self.teams.insert(id.clone(), team);
+            }
+        }
+        if seated.len() >= 2 {
+            let team = self.smaller_team();
+            if self.members(team).is_empty() {
+                if let Some(id) = seated.iter().rev().find(|id| self.teams.get(*id) != Some(&team)) {
+                    self.teams.insert(id.clone(), team);
+                }
+            }
+        }
+        let red = self.team_seats(Team::Red);
+        let blue = self.team_seats(Team::Blue);
+        self.team_order = (0..red.len().max(blue.len()))
+            .flat_map(|i| red.get(i).into_iter().chain(blue.get(i)))
+            .copied()
+            .collect();
+    }
+
+    /// 团队模式下中途坐下的玩家进人少的队，排到画图顺序的最后
+    pub(crate) fn assign_late_team(&mut self, user_id: &String) {
+        if !self.settings.team_mode {
+            return
+        }
+        let pos = match self.seat.iter().position(|id| id.as_ref() == Some(user_id)) {
+            Some(pos) => pos,
+            None => return,
+        };
+        if !self.teams.contains_key(user_id) {
+            let team = self.smaller_team();
+            self.teams.insert(user_id.clone(), team);
+        }
+        // 座位原来的顺序可能已经过去了，挪到最后
+        if self.cur_seat != Some(pos) {
+            self.team_order.retain(|p| *p != pos);
+            self.team_order.push(pos);
+        }
+    }
+
+    fn smaller_team(&self) -> Team {
+        if self.members(Team::Blue).len() < self.members(Team::Red).len() {
+            Team::Blue
+        } else {
+            Team::Red
+        }
+    }
+
+    fn members(&self, team: Team) -> Vec<&String> {
+        self.teams.iter()
+            .filter(|(_, t)| **t == team)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn team_seats(&self, team: Team) -> Vec<usize> {
+        self.seat.iter()
+            .enumerate()
+            .filter(|(_, id)| id.as_ref().and_then(|id| self.teams.get(id)) == Some(&team))
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    /// 按队伍交替的顺序找下一个还在座位上的玩家
+    pub(crate) fn next_team_seat(&self) -> Option<usize> {
+        let start = match self.cur_seat {
+            Some(cur) => self.team_order.iter().position(|pos| *pos == cur)? + 1,
+            None => 0,
+        };
+        self.team_order[start..].iter()
+            .copied()
+            .find(|pos| self.seat[*pos].is_some())
+    }
+
+    /// 猜词的玩家与画图玩家是否同队，对方队伍在开始绘制 `steal_delay` 秒后才能抢答
+    pub(crate) fn guess_kind(&self, user_id: &String, now: i64) -> GuessKind {
+        if !self.settings.team_mode {
+            return GuessKind::Normal
+        }
+        let drawer_team = self.cur_id.as_ref().and_then(|id| self.team_of(id));
+        if drawer_team.is_none() || self.team_of(user_id) == drawer_team {
+            return GuessKind::Normal
+        }
+        let steal_at = self.draw_started_at.unwrap_or(now) + self.settings.steal_delay as i64 * 1000;
+        if now >= steal_at {
+            GuessKind::Steal
+        } else {
+            GuessKind::TooEarly
+        }
+    }
+
+    /// 本回合答对的队友人数
+    pub(crate) fn teammates_guessed(&self, drawer_id: &String) -> usize {
+        let team = self.team_of(drawer_id);
+        self.round_score_map.keys()
+            .filter(|id| team.is_some() && self.team_of(id) == team)
+            .count()
+    }
+
+    /// 各队总分
+    pub fn team_settlement(&self) -> HashMap<Team, u16> {
+        if !self.settings.team_mode {
+            return HashMap::new()
+        }
+        Team::ALL.iter()
+            .map(|team| {
+                let total = self.settlement.iter()
+                    .filter(|(id, _)| self.team_of(id) == Some(*team))
+                    .map(|(_, score)| *score)
+                    .sum();
+                (*team, total)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_team_mode() {
+    use crate::room::SettingsPatch;
+
+    let mut room = RoomData::with_players(0..4);
+    let id = |id: &str| id.to_string();
+    assert_eq!(room.choose_team(&id("0"), Team::Red), Err(ApiError::WrongStage));
+    room.update_settings(&id("0"), SettingsPatch { team_mode: Some(true), steal_delay: Some(10), ..Default::default() }).unwrap();
+    room.choose_team(&id("0"), Team::Blue).unwrap();
+    room.choose_team(&id("1"), Team::Blue).unwrap();
+
+    room.start();
+    assert_eq!(room.team_of(&id("0")), Some(Team::Blue));
+    assert_eq!(room.members(Team::Red).len(), 2);
+    // 红蓝交替画图
+    let mut order = vec![room.cur_id.clone().unwrap()];
+    while room.next_player() {
+        order.push(room.cur_id.clone().unwrap());
+    }
+    assert_eq!(order, vec!["2", "0", "3", "1"]);
+
+    room.start();
+    room.pick_a_word(&id("2"), "苹果".to_string()).unwrap();
+    let started = room.draw_started_at.unwrap();
+    assert_eq!(room.guess_kind(&id("3"), started), GuessKind::Normal);
+    assert_eq!(room.guess_kind(&id("0"), started), GuessKind::TooEarly);
+    assert_eq!(room.guess_kind(&id("0"), started + 10_000), GuessKind::Steal);
+
+    room.add_chat(id("3"), "苹果".to_string());
+    room.draw_end();
+    assert_eq!(room.settlement.get("3"), Some(&6));
+    assert_eq!(room.settlement.get("2"), Some(&TEAMMATE_BONUS));
+    assert_eq!(room.team_settlement().get(&Team::Red), Some(&(6 + TEAMMATE_BONUS)));
+}
+
+#[test]
+fn test_team_late_join() {
+    use crate::{late_join::MidGameJoin, player::Player, room::SettingsPatch};
+
+    let mut room = RoomData::with_players(&["0", "1", "2", "3"]);
+    let id = |id: &str| id.to_string();
+    room.update_settings(&id("0"), SettingsPatch {
+        team_mode: Some(true),
+        mid_game_join: Some(MidGameJoin::Seat),
+        ..Default::default()
+    }).unwrap();
+    room.start();
+    assert_eq!(room.team_order, vec![0, 1, 2, 3]);
+
+    // 中途坐下的玩家进人少的队，本局最后画图
+    room.join_player(&Player::test("4"));
+    assert_eq!(room.seat[4].as_deref(), Some("4"));
+    assert_eq!(room.team_of(&id("4")), Some(Team::Red));
+    assert_eq!(room.team_order, vec![0, 1, 2, 3, 4]);
+    let mut order = vec![room.cur_id.clone().unwrap()];
+    while room.next_player() {
+        order.push(room.cur_id.clone().unwrap());
+    }
+    assert_eq!(order, vec!["0", "1", "2", "3", "4"]);
+}
@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{
+    draw_data::DrawData,
+    error::{ApiError, ApiResult},
+    room::{GameStage, RoomData},
+    snapshot::{PollSnapshot, StageSnapshot},
+};
+
+/// 写题目与看图描述的时间（秒）
+pub const TELEPHONE_TEXT_TIME: u8 = 40;
+/// 公布结果的时间（秒）
+pub const TELEPHONE_REVEAL_TIME: u8 = 60;
+/// 题目与描述的最大长度（字符）
+pub const MAX_TELEPHONE_TEXT_LEN: usize = 40;
+/// 传话模式的最少人数
+pub const MIN_TELEPHONE_PEOPLE: usize = 2;
+/// 超时没有填写时的占位文字
+static EMPTY_TEXT: &str = "（没有填写）";
+
+/// 传话模式的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TelephoneStage {
+    /// 每人写一个题目
+    Write,
+    /// 画上一个人的题目或描述
+    Draw,
+    /// 描述上一个人的画
+    Describe,
+    /// 公布每条传话链
+    Reveal,
+}
+
+/// 传话链上的一项
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ChainEntry {
+    Text {
+        #[serde(rename = "authorId")]
+        author_id: String,
+        text: String,
+    },
+    Drawing {
+        #[serde(rename = "authorId")]
+        author_id: String,
+        #[serde(rename = "drawData")]
+        draw_data: DrawData,
+    },
+}
+
+/// 一条传话链，从 `owner_id` 写的题目开始
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Chain {
+    pub owner_id: String,
+    pub entries: Vec<ChainEntry>,
+}
+
+/// 进行中的传话游戏
+#[derive(Debug)]
+pub struct TelephoneGame {
+    pub stage: TelephoneStage,
+    /// 第几步，从 0 开始，共 `players.len()` 步
+    pub step: usize,
+    /// 开局时座位上的玩家，也是传递的顺序
+    pub players: Vec<String>,
+    /// 与 `players` 一一对应
+    pub chains: Vec<Chain>,
+    /// 本步已经提交的玩家
+    pub submitted: HashSet<String>,
+    /// 本步结束的时间戳
+    pub deadline: i64,
+    /// 序号，用于匹配步骤定时器
+    pub seq: u32,
+}
+
+impl TelephoneGame {
+    /// 玩家本步处理的链条：第 `step` 步处理往前数第 `step` 个人的链条
+    fn chain_of(&self, user_id: &String) -> Option<usize> {
+        let n = self.players.len();
+        let index = self.players.iter().position(|id| id == user_id)?;
+        Some((index + n - self.step % n) % n)
+    }
+}
+
+/// 传话模式的快照
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TelephoneSnapshot {
+    pub telephone_stage: TelephoneStage,
+    pub step: usize,
+    pub steps: usize,
+    pub deadline: i64,
+    pub submitted: Vec<String>,
+    /// 本步要画的文字或要描述的画，只有参与的玩家有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<ChainEntry>,
+    /// 自己正在画的画布
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canvas: Option<DrawData>,
+    /// 公布阶段的全部链条
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chains: Vec<Chain>,
+}
+
+impl RoomData {
+    /// 开始传话游戏，返回步骤序号与本步时长
+    pub fn start_telephone(&mut self, now: i64) -> ApiResult<(u32, u8)> {
+        let players = self.seat.iter().flatten().cloned().collect::<Vec<_>>();
+        if players.len() < MIN_TELEPHONE_PEOPLE {
+            return Err(ApiError::NotEnoughPlayers)
+        }
+        self.reset_ready();
+        self.stage = GameStage::Telephone;
+        self.telephone_seq += 1;
+        self.telephone = Some(TelephoneGame {
+            stage: TelephoneStage::Write,
+            step: 0,
+            chains: players.iter()
+                .map(|id| Chain { owner_id: id.clone(), entries: vec![] })
+                .collect(),
+            players,
+            submitted: HashSet::new(),
+            deadline: now + TELEPHONE_TEXT_TIME as i64 * 1000,
+            seq: self.telephone_seq,
+        });
+        self.next_timestamp = Some(now + TELEPHONE_TEXT_TIME as i64 * 1000);
+        Ok((self.telephone_seq, TELEPHONE_TEXT_TIME))
+    }
+
+    /// 提交本步的文字，画图的步骤提交表示画完了；返回是否所有人都提交了
+    pub fn telephone_submit(&mut self, user_id: &String, text: Option<String>) -> ApiResult<bool> {
+        if self.stage != GameStage::Telephone {
+            return Err(ApiError::WrongStage)
+        }
+        let game = self.telephone.as_mut().ok_or(ApiError::WrongStage)?;
+        let chain = game.chain_of(user_id).ok_or(ApiError::NotSeated)?;
+        match game.stage {
+            TelephoneStage::Write | TelephoneStage::Describe => {
+                let text = text.map(|text| text.trim().to_string())
+                    .filter(|text| !text.is_empty() && text.chars().count() <= MAX_TELEPHONE_TEXT_LEN)
+                    .ok_or(ApiError::InvalidPayload)?;
+                let entries = &mut game.chains[chain].entries;
+                // 可以修改已经提交的文字
+                if game.submitted.contains(user_id) {
+                    entries.pop();
+                }
+                entries.push(ChainEntry::Text { author_id: user_id.clone(), text });
+            },
+            TelephoneStage::Draw => (),
+            TelephoneStage::Reveal => return Err(ApiError::WrongStage),
+        }
+        game.submitted.insert(user_id.clone());
+        let players = &self.players;
+        Ok(game.players.iter()
+            .filter(|id| players.contains_key(*id))
+            .all(|id| game.submitted.contains(id)))
+    }
+
+    /// 画图步骤中自己的画布
+    pub fn telephone_canvas(&mut self, user_id: &String) -> ApiResult<&mut DrawData> {
+        let game = self.telephone.as_mut().ok_or(ApiError::WrongStage)?;
+        if game.stage != TelephoneStage::Draw {
+            return Err(ApiError::WrongStage)
+        }
+        let chain = game.chain_of(user_id).ok_or(ApiError::NotSeated)?;
+        match game.chains[chain].entries.last_mut() {
+            Some(ChainEntry::Drawing { draw_data, .. }) => Ok(draw_data),
+            _ => Err(ApiError::Internal),
+        }
+    }
+
+    /// 进入下一步，`seq` 不一致说明该步已经结束；返回新步骤的序号与时长，游戏结束时返回 `None`
+    pub fn advance_telephone(&mut self, seq: u32, now: i64) -> Option<(u32, u8)> {
+        let draw_time = self.settings.draw_time;
+        let game = self.telephone.as_mut().filter(|game| game.seq == seq)?;
+        if game.stage == TelephoneStage::Reveal {
+            self.telephone = None;
+            self.stage = GameStage::Ready;
+            self.next_timestamp = Some(0);
+            return None
+        }
+        // 没有提交文字的玩家用占位文字
+        if game.stage != TelephoneStage::Draw {
+            for id in game.players.clone() {
+                if !game.submitted.contains(&id) {
+                    if let Some(chain) = game.chain_of(&id) {
+                        game.chains[chain].entries.push(ChainEntry::Text { author_id: id, text: EMPTY_TEXT.to_string() });
+                    }
+                }
+            }
+        }
+        game.submitted.clear();
+        game.step += 1;
+        let sec = if game.step >= game.players.len() {
+            game.stage = TelephoneStage::Reveal;
+            TELEPHONE_REVEAL_TIME
+        } else if game.step % 2 == 1 {
+            game.stage = TelephoneStage::Draw;
+            for id in game.players.clone() {
+                if let Some(chain) = game.chain_of(&id) {
+                    game.chains[chain].entries.push(ChainEntry::Drawing { author_id: id, draw_data: DrawData::default() });
+                }
+            }
+            draw_time
+        } else {
+            game.stage = TelephoneStage::Describe;
+            TELEPHONE_TEXT_TIME
+        };
+        self.telephone_seq += 1;
+        game.seq = self.telephone_seq;
+        game.deadline = now + sec as i64 * 1000;
+        self.next_timestamp = Some(game.deadline);
+        Some((game.seq, sec))
+    }
+
+    /// 不含个人任务的快照，公布阶段附带全部链条
+    pub fn telephone_snapshot(&self) -> Option<TelephoneSnapshot> {
+        let game = self.telephone.as_ref()?;
+        Some(TelephoneSnapshot {
+            telephone_stage: game.stage,
+            step: game.step,
+            steps: game.players.len(),
+            deadline: game.deadline,
+            submitted: game.submitted.iter().cloned().collect(),
+            task: None,
+            canvas: None,
+            chains: if game.stage == TelephoneStage::Reveal {
+                game.chains.clone()
+            } else {
+                vec![]
+            },
+        })
+    }
+
+    /// 给轮训快照补上玩家本步的任务：上一个人写的文字或画的画
+    pub fn attach_telephone_task(&self, snapshot: &mut PollSnapshot, user_id: &String) {
+        let (game, telephone) = match (self.telephone.as_ref(), &mut snapshot.detail) {
+            (Some(game), StageSnapshot::Telephone(telephone)) => (game, telephone),
+            _ => return,
+        };
+        let entries = match game.chain_of(user_id) {
+            Some(chain) if game.stage != TelephoneStage::Reveal => &game.chains[chain].entries,
+            _ => return,
+        };
+        match game.stage {
+            TelephoneStage::Draw => {
+                telephone.task = entries.iter().rev().nth(1).cloned();
+                if let Some(ChainEntry::Drawing { draw_data, .. }) = entries.last() {
+                    telephone.canvas = Some(draw_data.clone());
+                }
+            },
+            TelephoneStage::Describe => {
+                // 已经提交时最后一项是自己的描述
+                let skip = usize::from(game.submitted.contains(user_id));
+                telephone.task = entries.iter().rev().nth(skip).cloned();
+            },
+            TelephoneStage::Write | TelephoneStage::Reveal => (),
+        }
+    }
+}
+
+#[test]
+fn test_telephone() {
+    use crate::draw_data::DrawOp;
+
+    let mut room = RoomData::with_players(0..3);
+    let id = |id: &str| id.to_string();
+    let (seq, _) = room.start_telephone(0).unwrap();
+    assert_eq!(room.stage, GameStage::Telephone);
+    assert_eq!(room.telephone_submit(&id("0"), Some("苹果".to_string())), Ok(false));
+    assert_eq!(room.telephone_submit(&id("1"), Some("".to_string())), Err(ApiError::InvalidPayload));
+    assert_eq!(room.telephone_submit(&id("1"), Some("香蕉".to_string())), Ok(false));
+
+    // 2 号没写，超时用占位文字
+    let (seq, _) = room.advance_telephone(seq, 0).unwrap();
+    assert!(room.advance_telephone(seq - 1, 0).is_none());
+    assert_eq!(room.telephone.as_ref().unwrap().stage, TelephoneStage::Draw);
+    // 1 号画 0 号的题目
+    room.draw_op(&id("1"), DrawOp::Path { data: "2;#000000;1|".to_string() }, 10).unwrap();
+    let mut snapshot = room.snapshot(room.viewer(Some("1")), 0, 0);
+    room.attach_telephone_task(&mut snapshot, &id("1"));
+    match &snapshot.detail {
+        StageSnapshot::Telephone(telephone) => {
+            assert!(matches!(&telephone.task, Some(ChainEntry::Text { text, .. }) if text == "苹果"));
+            assert_eq!(telephone.canvas.as_ref().map(|canvas| canvas.len()), Some(1));
+            assert!(telephone.chains.is_empty());
+        },
+        _ => panic!("not telephone"),
+    }
+
+    let (seq, _) = room.advance_telephone(seq, 0).unwrap();
+    assert_eq!(room.telephone.as_ref().unwrap().stage, TelephoneStage::Describe);
+    let (seq, _) = room.advance_telephone(seq, 0).unwrap();
+    let game = room.telephone.as_ref().unwrap();
+    assert_eq!(game.stage, TelephoneStage::Reveal);
+    assert!(game.chains.iter().all(|chain| chain.entries.len() == 3));
+    assert!(matches!(&game.chains[0].entries[1], ChainEntry::Drawing { author_id, draw_data } if author_id == "1" && draw_data.len() == 1));
+
+    assert!(room.advance_telephone(seq, 0).is_none());
+    assert_eq!(room.stage, GameStage::Ready);
+    assert!(room.telephone.is_none());
+}
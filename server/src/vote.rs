@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{ApiError, ApiResult}, room::{GameStage, RoomData}};
+
+/// 投票持续时间（秒）
+pub const VOTE_DURATION: u8 = 30;
+/// 同一玩家两次发起投票的最短间隔（秒）
+pub const VOTE_COOLDOWN: i64 = 60;
+
+/// 投票类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum VoteType {
+    Kick,
+    Skip,
+}
+
+/// 投票内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum VoteKind {
+    /// 踢出玩家
+    Kick {
+        #[serde(rename = "targetId")]
+        target_id: String,
+    },
+    /// 跳过当前玩家的回合
+    Skip {
+        #[serde(rename = "drawerId")]
+        drawer_id: String,
+    },
+}
+
+/// 正在进行的投票
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Vote {
+    /// 投票序号，用于匹配过期定时器
+    pub seq: u32,
+    #[serde(flatten)]
+    pub kind: VoteKind,
+    pub initiator: String,
+    pub yes: HashSet<String>,
+    pub no: HashSet<String>,
+    /// 通过所需的赞成票数
+    pub threshold: usize,
+    pub expires_at: i64,
+}
+
+/// 投票结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum VoteOutcome {
+    /// 还在进行中，附带投票序号
+    Pending(u32),
+    Passed(VoteKind),
+    Failed,
+}
+
+impl VoteKind {
+    /// 被投票的玩家，不参与投票
+    fn subject(&self) -> &String {
+        match self {
+            VoteKind::Kick { target_id } => target_id,
+            VoteKind::Skip { drawer_id } => drawer_id,
+        }
+    }
+}
+
+impl RoomData {
+    fn is_seated(&self, user_id: &String) -> bool {
+        self.seat.iter().any(|seat| seat.as_ref() == Some(user_id))
+    }
+
+    fn nick_name(&self, user_id: &String) -> String {
+        self.players.get(user_id).map_or_else(|| user_id.clone(), |player| player.nick_name.clone())
+    }
+
+    /// 座位上除被投票玩家以外的人过半数通过
+    fn vote_threshold(&self, subject: &String) -> usize {
+        let voters = self.seat.iter()
+            .flatten()
+            .filter(|id| *id != subject)
+            .count();
+        voters / 2 + 1
+    }
+
+    /// 发起投票，发起人默认赞成
+    pub fn start_vote(&mut self, user_id: &String, vote_type: VoteType, target_id: Option<String>, now: i64) -> ApiResult<VoteOutcome> {
+        if !self.is_seated(user_id) {
+            return Err(ApiError::NotSeated)
+        }
+        if self.vote.is_some() {
+            return Err(ApiError::VoteInProgress)
+        }
+        if let Some(last) = self.vote_cooldown.get(user_id) {
+            if now - last < VOTE_COOLDOWN * 1000 {
+                return Err(ApiError::VoteCooldown)
+            }
+        }
+        let kind = match vote_type {
+            VoteType::Kick => {
+                let target_id = target_id.ok_or(ApiError::InvalidPayload)?;
+                if &target_id == user_id {
+                    return Err(ApiError::InvalidPayload)
+                }
+                if !self.players.contains_key(&target_id) {
+                    return Err(ApiError::NotInRoom)
+                }
+                VoteKind::Kick { target_id }
+            },
+            VoteType::Skip => {
+                if !matches!(self.stage, GameStage::Choose | GameStage::Drawing) {
+                    return Err(ApiError::WrongStage)
+                }
+                let drawer_id = self.cur_id.clone().ok_or(ApiError::WrongStage)?;
+                if &drawer_id == user_id {
+                    return Err(ApiError::InvalidPayload)
+                }
+                VoteKind::Skip { drawer_id }
+            },
+        };
+        self.vote_seq += 1;
+        self.vote_cooldown.insert(user_id.clone(), now);
+        let subject = self.nick_name(kind.subject());
+        let content = match &kind {
+            VoteKind::Kick { .. } => format!("{}发起投票踢出玩家{}", self.nick_name(user_id), subject),
+            VoteKind::Skip { .. } => format!("{}发起投票跳过玩家{}的回合", self.nick_name(user_id), subject),
+        };
+        self.add_system_chat(content);
+        self.vote = Some(Vote {
+            seq: self.vote_seq,
+            threshold: self.vote_threshold(kind.subject()),
+            kind,
+            initiator: user_id.clone(),
+            yes: vec![user_id.clone()].into_iter().collect(),
+            no: HashSet::new(),
+            expires_at: now + VOTE_DURATION as i64 * 1000,
+        });
+        Ok(self.tally_vote())
+    }
+
+    /// 投票，可以改票
+    pub fn cast_vote(&mut self, user_id: &String, agree: bool) -> ApiResult<VoteOutcome> {
+        if !self.is_seated(user_id) {
+            return Err(ApiError::NotSeated)
+        }
+        let vote = self.vote.as_mut().ok_or(ApiError::NoActiveVote)?;
+        if vote.kind.subject() == user_id {
+            return Err(ApiError::InvalidPayload)
+        }
+        if agree {
+            vote.no.remove(user_id);
+            vote.yes.insert(user_id.clone());
+        } else {
+            vote.yes.remove(user_id);
+            vote.no.insert(user_id.clone());
+        }
+        Ok(self.tally_vote())
+    }
+
+    /// 统计票数，离开座位的玩家的票不算
+    fn tally_vote(&mut self) -> VoteOutcome {
+        let vote = match self.vote.take() {
+            Some(vote) => vote,
+            None => return VoteOutcome::Failed,
+        };
+        let subject = vote.kind.subject().clone();
+        let threshold = self.vote_threshold(&subject);
+        let voters = self.seat.iter()
+            .flatten()
+            .filter(|id| **id != subject)
+            .collect::<Vec<_>>();
+        let yes = voters.iter().filter(|id| vote.yes.contains(**id)).count();
+        let no = voters.iter().filter(|id| vote.no.contains(**id)).count();
+        if yes >= threshold {
+            self.add_system_chat(format!("投票通过（{}/{}）", yes, voters.len()));
+            VoteOutcome::Passed(vote.kind)
+        } else if voters.len() - no < threshold {
+            self.add_system_chat(format!("投票未通过（{}/{}）", yes, voters.len()));
+            VoteOutcome::Failed
+        } else {
+            let seq = vote.seq;
+            self.vote = Some(Vote { threshold, ..vote });
+            VoteOutcome::Pending(seq)
+        }
+    }
+
+    /// 回合结束后，针对该回合的跳过投票作废
+    pub(crate) fn clear_skip_vote(&mut self) {
+        if matches!(&self.vote, Some(Vote { kind: VoteKind::Skip { .. }, .. })) {
+            self.vote = None;
+        }
+    }
+
+    /// 投票到期，`seq` 不一致说明该投票已经结束
+    pub fn expire_vote(&mut self, seq: u32) -> bool {
+        if self.vote.as_ref().map(|vote| vote.seq) == Some(seq) {
+            self.vote = None;
+            self.add_system_chat("投票超时，未通过".to_string());
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn test_vote() {
+    let mut room = RoomData::with_players(&["1", "2", "3", "4"]);
+    let id = |id: &str| id.to_string();
+
+    // 踢出 4 号：除 4 号外 3 人，需要 2 票
+    assert_eq!(room.start_vote(&id("1"), VoteType::Kick, Some(id("4")), 0), Ok(VoteOutcome::Pending(1)));
+    assert_eq!(room.start_vote(&id("2"), VoteType::Kick, Some(id("4")), 0), Err(ApiError::VoteInProgress));
+    assert_eq!(room.cast_vote(&id("4"), false), Err(ApiError::InvalidPayload));
+    assert_eq!(room.cast_vote(&id("2"), true), Ok(VoteOutcome::Passed(VoteKind::Kick { target_id: id("4") })));
+    assert!(room.vote.is_none());
+
+    // 冷却时间内不能再发起
+    assert_eq!(room.start_vote(&id("1"), VoteType::Kick, Some(id("3")), 1000), Err(ApiError::VoteCooldown));
+
+    // 非游戏阶段不能跳过
+    assert_eq!(room.start_vote(&id("2"), VoteType::Skip, None, 0), Err(ApiError::WrongStage));
+    room.stage = GameStage::Drawing;
+    room.cur_id = Some(id("1"));
+    assert_eq!(room.start_vote(&id("2"), VoteType::Skip, None, 0), Ok(VoteOutcome::Pending(2)));
+    assert_eq!(room.cast_vote(&id("3"), false), Ok(VoteOutcome::Pending(2)));
+    assert_eq!(room.cast_vote(&id("4"), false), Ok(VoteOutcome::Failed));
+
+    assert_eq!(room.start_vote(&id("3"), VoteType::Skip, None, VOTE_COOLDOWN * 1000), Ok(VoteOutcome::Pending(3)));
+    assert!(!room.expire_vote(2));
+    assert!(room.expire_vote(3));
+    assert_eq!(room.cast_vote(&id("2"), true), Err(ApiError::NoActiveVote));
+
+    // 回合结束或轮到下一位时，跳过投票作废
+    assert_eq!(room.start_vote(&id("4"), VoteType::Skip, None, 0), Ok(VoteOutcome::Pending(4)));
+    room.draw_end();
+    assert!(room.vote.is_none());
+    room.stage = GameStage::Choose;
+    room.cur_seat = Some(0);
+    assert_eq!(room.start_vote(&id("2"), VoteType::Skip, None, VOTE_COOLDOWN * 2000), Ok(VoteOutcome::Pending(5)));
+    room.next_player();
+    assert_ne!(room.cur_id, Some(id("1")));
+    assert!(room.vote.is_none());
+}
@@ -1,6 +1,21 @@
-use std::{fs::File, io::Read};
+use std::{collections::{BTreeMap, HashMap}, fs, io, path::Path, sync::{Arc, RwLock}};
 
+use once_cell::sync::Lazy;
 use rand::prelude::SliceRandom;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::room::{is_valid_tag, DEFAULT_WORD_PACK};
+
+/// 词库目录，每个 `<词库名>.txt` 是一个词库
+pub const WORD_PACK_DIR: &str = "words";
+/// 旧版的单文件词库，作为默认词库
+pub const LEGACY_WORD_FILE: &str = "words.txt";
+/// 每次给出的候选词数量，也是一个词库最少的词数
+pub const WORD_OFFER_COUNT: usize = 6;
+
+/// 当前使用的词库，重新加载时整体替换
+static WORD_PACKS: Lazy<RwLock<Arc<WordPacks>>> = Lazy::new(Default::default);
 
 #[derive(Debug)]
 pub struct WordEngine {
@@ -8,13 +23,11 @@ pub struct WordEngine {
 }
 
 impl WordEngine {
-    pub fn configure(filename: &str) -> Self {
-        let mut file = File::open(filename).expect("打开词库文件失败");
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).expect("读取词库文件失败");
-        let vec = buffer.split("\n")
-            .filter(|a| a.len() > 0)
+    /// 解析词库文件，每行一个词
+    pub fn parse(content: &str) -> Self {
+        let vec = content.split('\n')
             .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty())
             .collect::<Vec<String>>();
         Self {
             words: vec,
@@ -26,4 +39,109 @@ impl WordEngine {
             .collect::<Vec<_>>();
         vec
     }
-}
\ No newline at end of file
+}
+
+/// 全部词库
+#[derive(Debug, Default)]
+pub struct WordPacks {
+    packs: HashMap<String, WordEngine>,
+}
+
+impl WordPacks {
+    /// 加载词库目录与旧版词库文件，目录中的同名词库优先；
+    /// 任何文件读取失败、词数不足或缺少默认词库都视为无效
+    pub fn load(dir: &Path, legacy: &Path) -> io::Result<Self> {
+        let mut packs = HashMap::new();
+        if legacy.exists() {
+            packs.insert(DEFAULT_WORD_PACK.to_string(), Self::load_file(legacy)?);
+        }
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().map_or(true, |ext| ext != "txt") {
+                    continue
+                }
+                let name = path.file_stem()
+                    .and_then(|name| name.to_str())
+                    .filter(|name| is_valid_tag(name))
+                    .ok_or_else(|| invalid(format!("词库文件名不合法: {}", path.display())))?
+                    .to_string();
+                packs.insert(name, Self::load_file(&path)?);
+            }
+        }
+        if !packs.contains_key(DEFAULT_WORD_PACK) {
+            return Err(invalid(format!("缺少默认词库 {}", DEFAULT_WORD_PACK)))
+        }
+        Ok(Self { packs })
+    }
+
+    fn load_file(path: &Path) -> io::Result<WordEngine> {
+        let engine = WordEngine::parse(&fs::read_to_string(path)?);
+        if engine.words.len() < WORD_OFFER_COUNT {
+            return Err(invalid(format!("词库 {} 少于 {} 个词", path.display(), WORD_OFFER_COUNT)))
+        }
+        Ok(engine)
+    }
+
+    /// 从指定词库随机取词，词库不存在时用默认词库
+    pub fn random(&self, pack: &str, amount: usize) -> Vec<String> {
+        self.packs.get(pack)
+            .or_else(|| self.packs.get(DEFAULT_WORD_PACK))
+            .map(|engine| engine.random(amount).into_iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 各词库的词数
+    pub fn summary(&self) -> BTreeMap<String, usize> {
+        self.packs.iter()
+            .map(|(name, engine)| (name.clone(), engine.words.len()))
+            .collect()
+    }
+}
+
+fn invalid(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// 当前的词库，取到的 `Arc` 不受之后重新加载的影响
+pub fn word_packs() -> Arc<WordPacks> {
+    WORD_PACKS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 重新加载词库，校验通过后才替换，失败时继续使用原有词库
+pub fn reload() -> io::Result<Arc<WordPacks>> {
+    let packs = Arc::new(WordPacks::load(Path::new(WORD_PACK_DIR), Path::new(LEGACY_WORD_FILE))?);
+    *WORD_PACKS.write().unwrap_or_else(|e| e.into_inner()) = packs.clone();
+    Ok(packs)
+}
+
+/// 重新加载后的词库
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReloadWordsResult {
+    /// 词库名与词数
+    pub packs: BTreeMap<String, usize>,
+}
+
+#[test]
+fn test_load_word_packs() {
+    let dir = std::env::temp_dir().join(format!("word-packs-{}", std::process::id()));
+    let packs_dir = dir.join("words");
+    fs::create_dir_all(&packs_dir).unwrap();
+    let legacy = dir.join("words.txt");
+    let words = "苹果\n香蕉\n西瓜\n\n葡萄\n橘子\n草莓\n";
+
+    // 没有默认词库
+    fs::write(packs_dir.join("animal.txt"), "猫\n狗\n鸡\n鸭\n鹅\n牛\n").unwrap();
+    assert!(WordPacks::load(&packs_dir, &legacy).is_err());
+
+    fs::write(&legacy, words).unwrap();
+    let packs = WordPacks::load(&packs_dir, &legacy).unwrap();
+    assert_eq!(packs.summary().get("default"), Some(&6));
+    assert_eq!(packs.random("animal", 2).len(), 2);
+    assert_eq!(packs.random("missing", 10).len(), 6);
+
+    // 词数不足的词库使整体加载失败
+    fs::write(packs_dir.join("food.txt"), "饺子\n").unwrap();
+    assert!(WordPacks::load(&packs_dir, &legacy).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
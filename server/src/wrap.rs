@@ -1,8 +1,10 @@
 use actix::{MailboxError};
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, ResponseError, http::StatusCode};
 use serde::Serialize;
 use serde_json::{Value, json};
 
+use crate::error::{ApiError, ApiResult};
+
 pub trait WrapMailboxErrorResult<T> {
     fn response<F, S: Serialize>(self, f: F) -> HttpResponse
     where
@@ -39,16 +41,93 @@ impl<T:Serialize> WrapMailboxErrorResponse<T> for Result<T, MailboxError> {
     }
 }
 
-impl WarpSuccResponse for Result<bool, MailboxError> {
+impl WarpSuccResponse for Result<ApiResult<()>, MailboxError> {
     fn to_succ_response(self) -> HttpResponse {
-        self.response(|succ| json!({ "succ": succ }))
+        self.api_response(|_| json!({ "succ": true }))
     }
 }
 
-pub trait WrapToValue {
-    fn to_value(&self) -> Value;
+pub trait WrapApiResult<T> {
+    fn api_response<F, S: Serialize>(self, f: F) -> HttpResponse
+    where
+        F: FnOnce(T) -> S;
+}
+
+pub trait WrapApiResponse<T> {
+    fn to_api_response(self) -> HttpResponse;
+}
+
+impl<T> WrapApiResult<T> for Result<ApiResult<T>, MailboxError> {
+    fn api_response<F, S: Serialize>(self, f: F) -> HttpResponse
+    where
+        F: FnOnce(T) -> S {
+        match self {
+            Ok(Ok(val)) => {
+                HttpResponse::Ok().json(f(val))
+            },
+            Ok(Err(e)) => e.error_response(),
+            Err(e) => {
+                eprintln!("Encounter MailboxError: {}", e);
+                ApiError::Internal.error_response()
+            }
+        }
+    }
 }
 
-pub trait PollingProvider {
-    fn snapshot(&self, user_id: &String, timestamp: i64, draw_length: usize) -> Value;
+impl<T: Serialize> WrapApiResponse<T> for Result<ApiResult<T>, MailboxError> {
+    fn to_api_response(self) -> HttpResponse {
+        self.api_response(|a| a)
+    }
+}
+
+/// 错误统一返回 `{ succ: false, code, message }`，状态码由错误类型决定
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status()).json(self.error_body(None))
+    }
+}
+
+impl ApiError {
+    /// 错误返回的内容，`reason` 为具体的失败原因
+    pub fn error_body(&self, reason: Option<&str>) -> Value {
+        let mut body = json!({
+            "succ": false,
+            "code": self.code(),
+            "message": self.message(),
+        });
+        if let Some(reason) = reason {
+            body["reason"] = json!(reason);
+        }
+        body
+    }
+
+    /// 带上具体失败原因的错误返回
+    pub fn error_response_with_reason(&self, reason: &str) -> HttpResponse {
+        HttpResponse::build(self.status()).json(self.error_body(Some(reason)))
+    }
+}
+
+#[test]
+fn test_error_response() {
+    for error in ApiError::ALL.iter() {
+        assert_eq!(error.error_response().status(), error.status());
+        assert_eq!(error.error_body(None)["code"], error.code());
+    }
+    assert_eq!(ApiError::RoomNotFound.status(), StatusCode::NOT_FOUND);
+    assert_eq!(ApiError::NotOwner.status(), StatusCode::FORBIDDEN);
+    assert_eq!(ApiError::SeatTaken.status(), StatusCode::CONFLICT);
+    assert_eq!(ApiError::InvalidPayload.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(ApiError::InvalidWordPack.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = ApiError::InvalidPayload.error_body(Some("negative offset in timeline"));
+    assert_eq!(body["succ"], false);
+    assert_eq!(body["code"], "INVALID_PAYLOAD");
+    assert_eq!(body["reason"], "negative offset in timeline");
+    assert!(ApiError::InvalidPayload.error_body(None).get("reason").is_none());
+    let response = ApiError::InvalidPayload.error_response_with_reason("bad json");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
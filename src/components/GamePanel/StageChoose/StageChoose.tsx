@@ -19,7 +19,9 @@ const StageChoose = () => {
         })
     }
     const onChangeWords = () => {
-        http.get('/random')
+        http.get('/random', {
+            params: { userId }
+        })
             .then(res => {
                 console.log(res.data)
                 if (Array.isArray(res.data)) {